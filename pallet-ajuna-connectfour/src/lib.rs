@@ -8,8 +8,9 @@ use frame_support::{
 	log,
 	traits::{
 		schedule::{DispatchTime, Named},
-		LockIdentifier, Randomness,
+		Get, LockIdentifier, Randomness,
 	},
+	weights::Weight,
 };
 //use frame_system::WeightInfo;
 use sp_runtime::traits::{Dispatchable, Hash, TrailingZeroInput};
@@ -18,7 +19,7 @@ use scale_info::TypeInfo;
 
 use sp_std::{prelude::*, vec::Vec};
 
-use pallet_matchmaker::MatchFunc;
+use pallet_matchmaker::{GameModeId, MatchFunc, MatchId, MatchResult, OnMatched};
 
 use log::info;
 
@@ -74,7 +75,6 @@ pub struct BoardStruct<Hash, AccountId, BlockNumber, BoardState> {
 
 const PLAYER_1: u8 = 1;
 const PLAYER_2: u8 = 2;
-const MAX_GAMES_PER_BLOCK: u8 = 10;
 const MAX_BLOCKS_PER_TURN: u8 = 10;
 const CLEANUP_BOARDS_AFTER: u8 = 20;
 
@@ -103,6 +103,10 @@ pub mod pallet {
 
 		type MatchMaker: MatchFunc<Self::AccountId>;
 
+		/// Constant game mode of the matchmaker, players of this game are queued in.
+		#[pallet::constant]
+		type GameMode: Get<GameModeId>;
+
 		// /// Weight information for extrinsics in this pallet.
 		//type WeightInfo: WeightInfo;
 	}
@@ -146,6 +150,11 @@ pub mod pallet {
 	pub type BoardSchedules<T: Config> =
 		StorageMap<_, Identity, T::Hash, Option<Vec<u8>>, ValueQuery>;
 
+	#[pallet::storage]
+	#[pallet::getter(fn board_match)]
+	/// Store the match of the boards the matchmaker created, only their results are rated.
+	pub type BoardMatches<T: Config> = StorageMap<_, Identity, T::Hash, MatchId, OptionQuery>;
+
 	// Default value for Nonce
 	#[pallet::type_value]
 	pub fn NonceDefault<T: Config>() -> u64 {
@@ -211,8 +220,6 @@ pub mod pallet {
 		NotPlayerTurn,
 		/// There was an error while trying to execute something in the logic mod.
 		WrongLogic,
-		/// Unable to queue, make sure you're not already queued.
-		AlreadyQueued,
 		/// Extrinsic is limited to founder.
 		OnlyFounderAllowed,
 	}
@@ -220,34 +227,6 @@ pub mod pallet {
 	// Pallet implements [`Hooks`] trait to define some logic to execute in some context.
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
-		// `on_initialize` is executed at the beginning of the block before any extrinsic are
-		// dispatched.
-		//
-		// This function must return the weight consumed by `on_initialize` and `on_finalize`.
-		fn on_initialize(_: T::BlockNumber) -> Weight {
-			// Anything that needs to be done at the start of the block.
-			// We don't do anything here.
-
-			// initial weights
-			let mut tot_weights = 10_000;
-			for _i in 0..MAX_GAMES_PER_BLOCK {
-				// try to create a match till we reached max games or no more matches available
-				let result = T::MatchMaker::try_match();
-				// if result is not empty we have a valid match
-				if !result.is_empty() {
-					// Create new game
-					let _game_id = Self::create_game(result[0].clone(), result[1].clone());
-					// weights need to be adjusted
-					tot_weights = tot_weights + T::DbWeight::get().reads_writes(1, 1);
-					continue
-				}
-				break
-			}
-
-			// return standard weigth for trying to fiond a match
-			return tot_weights
-		}
-
 		// `on_finalize` is executed at the end of block after all extrinsic are dispatched.
 		fn on_finalize(_n: BlockNumberFor<T>) {
 			// Perform necessary data/state clean up here.
@@ -317,15 +296,25 @@ pub mod pallet {
 		pub fn queue(origin: OriginFor<T>) -> DispatchResult {
 			let sender = ensure_signed(origin)?;
 
-			// Make sure player has no board open.
-			ensure!(!PlayerBoard::<T>::contains_key(&sender), Error::<T>::PlayerBoardExists);
-
-			let bracket: u8 = 0;
-			// Add player to queue, duplicate check is done in matchmaker.
-			if !T::MatchMaker::add_queue(sender, bracket) {
-				return Err(Error::<T>::AlreadyQueued)?
+			// Make sure no player of the party has a board open.
+			for member in T::MatchMaker::party_members(sender.clone()) {
+				ensure!(!PlayerBoard::<T>::contains_key(&member), Error::<T>::PlayerBoardExists);
 			}
 
+			// Add player to the queue of its rating bracket, errors are reported by matchmaker.
+			T::MatchMaker::add_queue_by_rating(sender, T::GameMode::get())?;
+
+			Ok(())
+		}
+
+		/// Remove sender from the queue, before being matched into a game.
+		#[pallet::weight(10_000 + T::MatchMaker::leave_queue_weight())]
+		pub fn leave_queue(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			// Remove player from queue, errors are reported by matchmaker.
+			T::MatchMaker::leave_queue(sender)?;
+
 			Ok(())
 		}
 
@@ -337,9 +326,8 @@ pub mod pallet {
 			// Make sure sender is founder.
 			ensure!(sender == Self::founder_key().unwrap(), Error::<T>::OnlyFounderAllowed);
 
-			let bracket: u8 = 0;
 			// Empty queues
-			T::MatchMaker::empty_queue(bracket);
+			T::MatchMaker::all_empty_queue(T::GameMode::get())?;
 
 			Ok(())
 		}
@@ -353,15 +341,15 @@ pub mod pallet {
 			ensure!(sender != opponent, Error::<T>::NoFakePlay);
 
 			// Don't allow queued player to create a game.
-			ensure!(!T::MatchMaker::is_queued(sender.clone()), Error::<T>::AlreadyQueued);
-			ensure!(!T::MatchMaker::is_queued(opponent.clone()), Error::<T>::AlreadyQueued);
+			T::MatchMaker::ensure_not_queued(sender.clone())?;
+			T::MatchMaker::ensure_not_queued(opponent.clone())?;
 
 			// Make sure players have no board open.
 			ensure!(!PlayerBoard::<T>::contains_key(&sender), Error::<T>::PlayerBoardExists);
 			ensure!(!PlayerBoard::<T>::contains_key(&opponent), Error::<T>::PlayerBoardExists);
 
-			// Create new game
-			let _board_id = Self::create_game(sender.clone(), opponent.clone());
+			// Create new game, boards the players didn't queue for aren't rated
+			let _board_id = Self::create_game(sender.clone(), opponent.clone(), None);
 
 			Ok(())
 		}
@@ -416,6 +404,13 @@ pub mod pallet {
 				board.board_state = BoardState::Finished(Default::default());
 			}
 
+			// Report finished board to update the players ratings and return their deposits.
+			if let BoardState::Finished(winner) = &board.board_state {
+				Self::report_result(&board_id, board.red.clone(), board.blue.clone(), winner);
+				T::MatchMaker::release_deposit(board.red.clone());
+				T::MatchMaker::release_deposit(board.blue.clone());
+			}
+
 			// get current blocknumber
 			let last_turn = <frame_system::Pallet<T>>::block_number();
 			board.last_turn = last_turn;
@@ -489,13 +484,21 @@ pub mod pallet {
 			ensure!(board.last_turn == last_turn, "There has been a move in between.");
 
 			if board.board_state == BoardState::Running {
-				if board.next_player == PLAYER_1 {
-					board.board_state = BoardState::Finished(board.blue.clone());
+				// the player at turn didn't act in time
+				let (winner, idle) = if board.next_player == PLAYER_1 {
+					(board.blue.clone(), board.red.clone())
 				} else if board.next_player == PLAYER_2 {
-					board.board_state = BoardState::Finished(board.red.clone());
+					(board.red.clone(), board.blue.clone())
 				} else {
 					return Err(Error::<T>::WrongLogic)?
-				}
+				};
+
+				// Report finished board to update the players ratings, the deposit of the
+				// idle player is slashed.
+				Self::report_result(&board_id, board.red.clone(), board.blue.clone(), &winner);
+				T::MatchMaker::release_deposit(winner.clone());
+				T::MatchMaker::slash_deposit(idle);
+				board.board_state = BoardState::Finished(winner);
 
 				// get current blocknumber
 				let last_turn = <frame_system::Pallet<T>>::block_number();
@@ -515,6 +518,7 @@ pub mod pallet {
 			} else {
 				// do cleanup after final force turn.
 				<Boards<T>>::remove(board_id);
+				<BoardMatches<T>>::remove(board_id);
 				<PlayerBoard<T>>::remove(board.red);
 				<PlayerBoard<T>>::remove(board.blue);
 				<BoardSchedules<T>>::remove(board_id);
@@ -542,7 +546,7 @@ impl<T: Config> Pallet<T> {
 	}
 
 	/// Generate a new game between two players.
-	fn create_game(red: T::AccountId, blue: T::AccountId) -> T::Hash {
+	fn create_game(red: T::AccountId, blue: T::AccountId, match_id: Option<MatchId>) -> T::Hash {
 		// get a random hash as board id
 		let board_id = Self::generate_random_hash(b"create", red.clone());
 
@@ -565,6 +569,9 @@ impl<T: Config> Pallet<T> {
 
 		// insert the new board into the storage
 		<Boards<T>>::insert(board_id, board);
+		if let Some(match_id) = match_id {
+			<BoardMatches<T>>::insert(board_id, match_id);
+		}
 
 		// Add board to the players playing it.
 		<PlayerBoard<T>>::insert(red, board_id);
@@ -577,6 +584,30 @@ impl<T: Config> Pallet<T> {
 		return board_id
 	}
 
+	/// Report the result of a finished board to the matchmaker, no winner is a draw.
+	///
+	/// Only boards of matchmaker matches are rated, players can't pick their opponents.
+	fn report_result(
+		board_id: &T::Hash,
+		red: T::AccountId,
+		blue: T::AccountId,
+		winner: &T::AccountId,
+	) {
+		if !<BoardMatches<T>>::contains_key(board_id) {
+			return
+		}
+
+		let result = if *winner == red {
+			MatchResult::Won
+		} else if *winner == blue {
+			MatchResult::Lost
+		} else {
+			MatchResult::Draw
+		};
+
+		T::MatchMaker::report_result(red, blue, result);
+	}
+
 	/// Schedule end turn
 	fn schedule_end_turn(
 		board_id: T::Hash,
@@ -603,3 +634,24 @@ impl<T: Config> Pallet<T> {
 		Some(schedule_task_id)
 	}
 }
+
+impl<T: Config> OnMatched<T::AccountId> for Pallet<T> {
+	fn on_matched(
+		mode: GameModeId,
+		match_id: MatchId,
+		players: Vec<T::AccountId>,
+	) -> (bool, Weight) {
+		// matches of other game modes are played elsewhere, a board has room for two
+		if mode != T::GameMode::get() || players.len() != 2 {
+			return (false, 0)
+		}
+		// a player has one board at a time, an open board is never overwritten
+		if players.iter().any(|player| PlayerBoard::<T>::contains_key(player)) {
+			return (false, T::DbWeight::get().reads(2))
+		}
+
+		let _game_id = Self::create_game(players[0].clone(), players[1].clone(), Some(match_id));
+		// weights need to be adjusted
+		(true, T::DbWeight::get().reads_writes(4, 5))
+	}
+}
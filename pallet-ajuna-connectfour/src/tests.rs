@@ -124,6 +124,11 @@ fn test_game_play() {
 		let board = ConnectFour::boards(board_id);
 		assert!(board.board_state == BoardState::Finished(board.blue));
 		assert_eq!(board.last_turn, current_block);
+
+		// boards the players didn't queue for aren't rated
+		assert_eq!(ConnectFour::board_match(board_id), None);
+		assert_eq!(MatchMaker::ratings(board.red), 1500);
+		assert_eq!(MatchMaker::ratings(board.blue), 1500);
 	});
 }
 
@@ -220,6 +225,9 @@ fn test_matchmaker_game() {
 		// start from block 100
 		run_to_block(current_block);
 
+		// matches that don't fit a board are left to other handlers
+		assert_eq!(ConnectFour::on_matched(0, 0, vec![PLAYER_1 as u64]), (false, 0));
+
 		// queue up player 1
 		assert_ok!(ConnectFour::queue(Origin::signed(PLAYER_1 as u64)));
 
@@ -229,7 +237,13 @@ fn test_matchmaker_game() {
 		// try to queue again same player 1
 		assert_noop!(
 			ConnectFour::queue(Origin::signed(PLAYER_1 as u64)),
-			Error::<Test>::AlreadyQueued
+			pallet_matchmaker::Error::<Test>::AlreadyQueued
+		);
+
+		// queued players can't start a game on their own
+		assert_noop!(
+			ConnectFour::new_game(Origin::signed(PLAYER_2 as u64), PLAYER_1 as u64),
+			pallet_matchmaker::Error::<Test>::AlreadyQueued
 		);
 
 		// queue up player 2
@@ -271,5 +285,64 @@ fn test_matchmaker_game() {
 		let board = ConnectFour::boards(board_id);
 		assert_eq!(board.last_turn, current_block);
 		assert!(board.board_state == BoardState::Finished(board.blue));
+
+		// check ratings of the matched players got updated with the result
+		assert!(ConnectFour::board_match(board_id).is_some());
+		assert_eq!(MatchMaker::ratings(board.red), 1484);
+		assert_eq!(MatchMaker::ratings(board.blue), 1516);
+	});
+}
+
+#[test]
+fn test_leave_queue() {
+	new_test_ext().execute_with(|| {
+		let current_block: u64 = 100;
+
+		// start from block 100
+		run_to_block(current_block);
+
+		// can't leave without being queued
+		assert_noop!(
+			ConnectFour::leave_queue(Origin::signed(PLAYER_1 as u64)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up player 1 and leave again
+		assert_ok!(ConnectFour::queue(Origin::signed(PLAYER_1 as u64)));
+		assert_ok!(ConnectFour::leave_queue(Origin::signed(PLAYER_1 as u64)));
+		assert_noop!(
+			ConnectFour::leave_queue(Origin::signed(PLAYER_1 as u64)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up player 2, no match as player 1 left
+		assert_ok!(ConnectFour::queue(Origin::signed(PLAYER_2 as u64)));
+
+		run_to_block(current_block + 1);
+
+		assert!(!PlayerBoard::<Test>::contains_key(PLAYER_1 as u64));
+		assert!(!PlayerBoard::<Test>::contains_key(PLAYER_2 as u64));
+	});
+}
+
+#[test]
+fn test_party_with_open_board() {
+	new_test_ext().execute_with(|| {
+		run_to_block(100);
+
+		// player 1 has a board open and joins the party of player 3
+		assert_ok!(ConnectFour::new_game(Origin::signed(PLAYER_1 as u64), PLAYER_2 as u64));
+		let board_id = ConnectFour::player_board(PLAYER_1 as u64);
+		assert_ok!(MatchMaker::create_party(Origin::signed(3)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(3), PLAYER_1 as u64));
+		assert_ok!(MatchMaker::join_party(Origin::signed(PLAYER_1 as u64), 3));
+
+		// the leader can't queue a party with a member that has a board open
+		assert_noop!(ConnectFour::queue(Origin::signed(3)), Error::<Test>::PlayerBoardExists);
+
+		// a match with a player that has a board open is refused, the board is kept
+		assert!(!ConnectFour::on_matched(0, 0, vec![PLAYER_1 as u64, 3]).0);
+		assert_eq!(ConnectFour::player_board(PLAYER_1 as u64), board_id);
+		assert!(!PlayerBoard::<Test>::contains_key(3));
 	});
 }
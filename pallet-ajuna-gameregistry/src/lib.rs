@@ -6,28 +6,35 @@
 use codec::{Decode, Encode};
 use frame_support::{
 	dispatch::DispatchResult,
-	log,
+	ensure, log,
 	traits::{
 		schedule::{DispatchTime, Named},
-		LockIdentifier, Randomness,
+		Currency, LockIdentifier, Randomness,
 	},
+	weights::Weight,
 };
 
 //use frame_system::WeightInfo;
 use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
 use sp_runtime::{
-	traits::{Dispatchable, Hash, TrailingZeroInput},
+	traits::{Dispatchable, Hash, SaturatedConversion, TrailingZeroInput},
 	RuntimeDebug,
 };
 use sp_std::vec::Vec;
 
-use pallet_matchmaker::MatchFunc;
+use pallet_matchmaker::{
+	BufferIndex, GameModeId, MatchFunc, MatchId, MatchResult, OnMatched, Rating, StorageDeque,
+};
 
 use log::info;
 
 // Re-export pallet items so that they can be accessed from the crate namespace.
 pub use pallet::*;
 
+mod migrations;
+
 #[cfg(test)]
 mod mock;
 
@@ -40,12 +47,10 @@ mod benchmarking;
 // importing the `weights.rs` here
 //pub mod weights;
 
-// importing queues, for game management
-mod queues;
-
-use queues::Queue;
-
 /// GameState structure, allowing Client & TEE to determine actions.
+///
+/// A game goes from `Waiting` to `Accepted`, `Running` and `Finished`, until it is finished it
+/// can be `Dropped` instead.
 #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
 pub enum GameState<AccountId> {
 	None,
@@ -53,18 +58,45 @@ pub enum GameState<AccountId> {
 	Accepted,
 	Running,
 	Finished(AccountId),
+	Dropped,
 }
 impl<AccountId> Default for GameState<AccountId> {
 	fn default() -> Self {
 		Self::None
 	}
 }
+impl<AccountId> GameState<AccountId> {
+	/// Return whether the game reached a state it can't leave anymore.
+	pub fn is_ended(&self) -> bool {
+		matches!(self, Self::Finished(_) | Self::Dropped)
+	}
+}
 
 /// Connect four board structure containing two players and the board
 #[derive(Encode, Decode, Default, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct GameEngine {
-	id: u8,
-	version: u8,
+	pub id: u8,
+	pub version: u8,
+}
+
+/// Lifecycle of a game engine in the catalogue.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+pub enum EngineStatus {
+	/// Players are queued for the game engine.
+	Active,
+	/// No players are queued anymore, matched players still get their games.
+	Deprecated,
+	/// No games are created anymore.
+	Retired,
+}
+
+/// Game engine registered in the catalogue, its players are matched in its game mode.
+#[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct EngineInfo {
+	pub game_mode: GameModeId,
+	pub status: EngineStatus,
+	pub metadata: Vec<u8>,
 }
 
 /// Connect four board structure containing two players and the board
@@ -80,9 +112,17 @@ pub struct GameEntry<Hash, AccountId, GameEngine, GameState, BlockNumber> {
 
 /// GameState structure, allowing Client & TEE to determine actions.
 #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub enum GameRuleType {
 	None,
+	/// Minimum and maximum amount of players of a game.
 	PlayersPerGame([u8; 2]),
+	/// Minimum matchmaker rating of each player.
+	MinimumRating(Rating),
+	/// Minimum free balance of each player.
+	RequiredBalance(u128),
+	/// Asset each player has to own.
+	OwnedAsset([u8; 16]),
 }
 impl Default for GameRuleType {
 	fn default() -> Self {
@@ -92,14 +132,36 @@ impl Default for GameRuleType {
 
 /// Connect four board structure containing two players and the board
 #[derive(Encode, Decode, Default, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct GameRule<GameRuleType> {
-	game_rule_type: GameRuleType,
-	game_rule_info: [u8; 16],
+	pub game_rule_type: GameRuleType,
+	pub game_rule_info: [u8; 16],
+}
+
+/// Tells whether an account owns an asset, for game engines requiring their players to own one.
+pub trait AssetOwnership<AccountId> {
+	fn owns(account: &AccountId, asset: &[u8; 16]) -> bool;
+}
+
+impl<AccountId> AssetOwnership<AccountId> for () {
+	fn owns(_account: &AccountId, _asset: &[u8; 16]) -> bool {
+		false
+	}
+}
+
+/// Registered Ajuna TEE enclave, allowed to run the games of its engine clusters.
+#[derive(Encode, Decode, Default, Clone, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct TeeEnclave<Hash, GameEngine> {
+	attestation: Hash,
+	clusters: Vec<GameEngine>,
+	active: bool,
 }
 
 const GAMEREGISTRY_ID: LockIdentifier = *b"gameregi";
-const MAX_GAMES_PER_BLOCK: u8 = 10;
 const MAX_QUEUE_SIZE: u8 = 64;
+const MAX_ACK_GAMES: usize = 100;
+const MAX_TEE_CLUSTERS: usize = 16;
+const MAX_ENGINE_METADATA: usize = 256;
 
 #[frame_support::pallet]
 pub mod pallet {
@@ -126,12 +188,25 @@ pub mod pallet {
 
 		type MatchMaker: MatchFunc<Self::AccountId>;
 
+		/// Currency of the players, checked by the balance requirements of the game engines.
+		type Currency: Currency<Self::AccountId>;
+
+		/// Assets of the players, checked by the asset requirements of the game engines.
+		type Assets: AssetOwnership<Self::AccountId>;
+
+		/// Origin allowed to register Ajuna TEE enclaves and manage the game engines.
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+
 		// /// Weight information for extrinsics in this pallet.
 		//type WeightInfo: WeightInfo;
 	}
 
+	/// The current storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T>(_);
 
 	// The pallet's runtime storage items.
@@ -148,10 +223,16 @@ pub mod pallet {
 	pub type FounderKey<T: Config> = StorageValue<_, T::AccountId>;
 
 	#[pallet::storage]
-	#[pallet::getter(fn game_queues)]
-	/// Store all queues for the games.
-	pub type GameQueues<T: Config> =
-		StorageMap<_, Identity, GameEngine, Queue<T::Hash>, ValueQuery>;
+	#[pallet::getter(fn game_queue_indices)]
+	/// Store the bounds of the queues for the games.
+	pub type GameQueueIndices<T: Config> =
+		StorageMap<_, Identity, GameEngine, (BufferIndex, BufferIndex), ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn game_queue_items)]
+	/// Store the queued games, by their index in the queue of the game engine.
+	pub type GameQueueItems<T: Config> =
+		StorageDoubleMap<_, Identity, GameEngine, Twox64Concat, BufferIndex, T::Hash, ValueQuery>;
 
 	#[pallet::storage]
 	#[pallet::getter(fn game_registry)]
@@ -170,6 +251,23 @@ pub mod pallet {
 	pub type GameRequirments<T: Config> =
 		StorageMap<_, Identity, GameEngine, Vec<GameRule<GameRuleType>>, ValueQuery>;
 
+	#[pallet::storage]
+	#[pallet::getter(fn game_engines)]
+	/// Store the catalogue of game engines and their versions.
+	pub type GameEngines<T: Config> = StorageMap<_, Identity, GameEngine, EngineInfo, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn engine_of_mode)]
+	/// Store the game engine the players of a matchmaker game mode are matched for.
+	pub type ModeEngines<T: Config> =
+		StorageMap<_, Twox64Concat, GameModeId, GameEngine, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn tee_enclaves)]
+	/// Store the registered Ajuna TEE enclaves, by their account.
+	pub type TeeEnclaves<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, TeeEnclave<T::Hash, GameEngine>, OptionQuery>;
+
 	// Default value for Nonce
 	#[pallet::type_value]
 	pub fn NonceDefault<T: Config>() -> u64 {
@@ -183,13 +281,15 @@ pub mod pallet {
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
 		pub founder_key: T::AccountId,
+		/// Initial game engines, with their game mode, metadata and rules.
+		pub engines: Vec<(GameEngine, GameModeId, Vec<u8>, Vec<GameRule<GameRuleType>>)>,
 	}
 
 	// The default value for the genesis config type.
 	#[cfg(feature = "std")]
 	impl<T: Config> Default for GenesisConfig<T> {
 		fn default() -> Self {
-			Self { founder_key: Default::default() }
+			Self { founder_key: Default::default(), engines: Default::default() }
 		}
 	}
 
@@ -198,6 +298,16 @@ pub mod pallet {
 	impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
 		fn build(&self) {
 			<FounderKey<T>>::put(&self.founder_key);
+
+			for (game_engine, game_mode, metadata, rules) in self.engines.iter() {
+				Pallet::<T>::do_register_engine(
+					game_engine.clone(),
+					*game_mode,
+					metadata.clone(),
+					rules.clone(),
+				)
+				.expect("genesis game engines must be valid");
+			}
 		}
 	}
 
@@ -213,9 +323,21 @@ pub mod pallet {
 		// Player has queued to play.
 		PlayerQueued(T::AccountId),
 
+		/// Player has left the queue.
+		PlayerLeftQueue(T::AccountId),
+
 		/// Game queued in waiting queue
 		GameQueued(GameEngine, T::Hash),
 
+		/// Game queue of the game engine is full, the players are queued again [engine]
+		GameQueueFull(GameEngine),
+
+		/// Game requirement failed by the players, others are queued again [engine, rule, players]
+		GameRuleFailed(GameEngine, GameRuleType, Vec<T::AccountId>),
+
+		/// Matched player couldn't be queued again, its party left the queue [player]
+		RequeueFailed(T::AccountId),
+
 		/// Amount of Games accepted by specific AjunaTEE
 		GamesAccepted(T::AccountId, u8),
 
@@ -224,6 +346,27 @@ pub mod pallet {
 
 		/// Game state changed to finished, with game winner
 		GameStateFinished(T::Hash, T::AccountId),
+
+		/// Game state changed to dropped, before the game finished
+		GameStateDropped(T::Hash),
+
+		/// Ajuna TEE enclave registered, with its attestation report hash [tee, attestation]
+		TeeRegistered(T::AccountId, T::Hash),
+
+		/// Ajuna TEE enclave activated or deactivated [tee, active]
+		TeeActiveSet(T::AccountId, bool),
+
+		/// Ajuna TEE enclave deregistered [tee]
+		TeeDeregistered(T::AccountId),
+
+		/// Game engine registered for a game mode [engine, mode]
+		EngineRegistered(GameEngine, GameModeId),
+
+		/// Game engine deprecated, players can't queue for it anymore [engine]
+		EngineDeprecated(GameEngine),
+
+		/// Game engine retired, no games are created for it anymore [engine]
+		EngineRetired(GameEngine),
 	}
 
 	// Errors inform users that something went wrong.
@@ -241,42 +384,47 @@ pub mod pallet {
 		NoGameQueue,
 		/// There is no such game entry
 		NoGameEntry,
-		/// Player is already queued for a match.
-		AlreadyQueued,
+		/// Sender is not a registered Ajuna TEE enclave.
+		NotRegisteredTee,
+		/// Ajuna TEE enclave is not active.
+		InactiveTee,
+		/// Ajuna TEE enclave does not serve the game engine of the game.
+		WrongTeeCluster,
+		/// To many engine clusters for an Ajuna TEE enclave.
+		TooManyClusters,
+		/// Game is not waiting to be acknowledged.
+		GameNotWaiting,
+		/// Game is not acknowledged.
+		GameNotAccepted,
+		/// Game is not running.
+		GameNotRunning,
+		/// Game is already finished or dropped.
+		GameEnded,
+		/// Sender is not the Ajuna TEE that acknowledged the game.
+		WrongTee,
+		/// Game engine is not in the catalogue.
+		UnknownEngine,
+		/// Game engine is already in the catalogue.
+		EngineAlreadyRegistered,
+		/// Game engine is deprecated or retired.
+		EngineNotActive,
+		/// Game engine is already retired.
+		EngineRetired,
+		/// Game mode is used by another game engine.
+		GameModeInUse,
+		/// Game engine metadata is too long.
+		MetadataTooLong,
+		/// Players don't meet the requirements of the game engine.
+		GameRuleFailed,
+		/// Game queue of the game engine is full.
+		GameQueueFull,
+		/// Game rule can't be met by the matches of the game mode.
+		InvalidGameRule,
 	}
 
 	// Pallet implements [`Hooks`] trait to define some logic to execute in some context.
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
-		// `on_initialize` is executed at the beginning of the block before any extrinsic are
-		// dispatched.
-		//
-		// This function must return the weight consumed by `on_initialize` and `on_finalize`.
-		fn on_initialize(_: T::BlockNumber) -> Weight {
-			// Anything that needs to be done at the start of the block.
-			// We don't do anything here.
-
-			// initial weights
-			let mut tot_weights = 10_000;
-			for _i in 0..MAX_GAMES_PER_BLOCK {
-				// try to create a match till we reached max games or no more matches available
-				let result = T::MatchMaker::try_match();
-				// if result is not empty we have a valid match
-				if !result.is_empty() {
-					let game_engine = GameEngine { id: 1u8, version: 1u8 };
-					// Create new game
-					let _game_id = Self::queue_game(game_engine, result);
-					// weights need to be adjusted
-					tot_weights = tot_weights + T::DbWeight::get().reads_writes(1, 1);
-					continue
-				}
-				break
-			}
-
-			// return standard weigth for trying to fiond a match
-			return tot_weights
-		}
-
 		// `on_finalize` is executed at the end of block after all extrinsic are dispatched.
 		fn on_finalize(_n: BlockNumberFor<T>) {
 			// Perform necessary data/state clean up here.
@@ -292,6 +440,10 @@ pub mod pallet {
 			// To see example on offchain worker, please refer to example-offchain-worker pallet
 			// accompanied in this repository.
 		}
+
+		fn on_runtime_upgrade() -> Weight {
+			migrations::v1::migrate::<T>()
+		}
 	}
 
 	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -341,18 +493,22 @@ pub mod pallet {
 			}
 		}
 
-		/// Queue sender up for a game, ranking brackets
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
-		pub fn queue(origin: OriginFor<T>) -> DispatchResult {
+		/// Queue sender up for a game of a game engine, ranking brackets
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,1))]
+		pub fn queue(origin: OriginFor<T>, game_engine: GameEngine) -> DispatchResult {
 			let sender = ensure_signed(origin)?;
 
 			// #TODO[MUST_HAVE, ALLREADY_REGISTRED] check if player is already in the game registry for a game.
 
-			let bracket: u8 = 0;
-			// Add player to queue, duplicate check is done in matchmaker.
-			if !T::MatchMaker::add_queue(sender.clone(), bracket) {
-				return Err(Error::<T>::AlreadyQueued)?
-			}
+			// only active game engines take new players
+			let engine = Self::game_engines(&game_engine).ok_or(Error::<T>::UnknownEngine)?;
+			ensure!(engine.status == EngineStatus::Active, Error::<T>::EngineNotActive);
+
+			// hold players back until the Ajuna TEE caught up with the waiting games
+			ensure!(!Self::is_game_queue_full(&game_engine), Error::<T>::GameQueueFull);
+
+			// Add player to the queue of its rating bracket, errors are reported by matchmaker.
+			T::MatchMaker::add_queue_by_rating(sender.clone(), engine.game_mode)?;
 
 			// Emit an event.
 			Self::deposit_event(Event::PlayerQueued(sender));
@@ -360,74 +516,103 @@ pub mod pallet {
 			Ok(())
 		}
 
-		/// Drop game will remove the game from the queue and the registry.
+		/// Remove sender from the queue, before being matched into a game.
+		#[pallet::weight(10_000 + T::MatchMaker::leave_queue_weight())]
+		pub fn leave_queue(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			// Remove player from queue, errors are reported by matchmaker.
+			T::MatchMaker::leave_queue(sender.clone())?;
+
+			// Emit an event.
+			Self::deposit_event(Event::PlayerLeftQueue(sender));
+
+			Ok(())
+		}
+
+		/// Drop game will remove the game from the queue and set state to dropped, a game
+		/// already finished or dropped is removed from the registry.
 		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
 		pub fn drop_game(
 			origin: OriginFor<T>,
 			game_hash: T::Hash,
 			game_engine: GameEngine,
 		) -> DispatchResult {
-			// #TODO[MUST_HAVE, SIGNATURE_CHECK] check that it's signed by a registred AjunaTEE.
-			let _who = ensure_signed(origin)?;
+			let who = ensure_signed(origin)?;
+			Self::ensure_tee(&who, &game_engine)?;
 
 			// retrieve game entry
 			if GameRegistry::<T>::contains_key(&game_hash) {
-				let _game_entry = GameRegistry::<T>::remove(&game_hash);
+				// only games of the game engine the Ajuna TEE serves can be dropped
+				let mut game_entry = Self::game_registry(&game_hash);
+				ensure!(game_entry.game_engine == game_engine, Error::<T>::WrongTeeCluster);
+
+				// ended games are only kept until they are dropped
+				if game_entry.game_state.is_ended() {
+					<GameRegistry<T>>::remove(&game_hash);
+					return Ok(())
+				}
+
+				Self::change_state(&mut game_entry, GameState::Dropped)?;
+
+				// insert changed game entry back
+				<GameRegistry<T>>::insert(game_hash, game_entry.clone());
 
-				let mut game_queue = Self::game_queues(&game_engine);
+				// players aren't to blame for a dropped game
+				for player in game_entry.players {
+					T::MatchMaker::release_deposit(player);
+				}
 
 				// check if there is any elements queued
-				if game_queue.length() > 0 {
-					// remove element
-					game_queue.remove(game_hash);
-					// insert into waiting queue for Ajuna TEE
-					<GameQueues<T>>::insert(game_engine, game_queue);
+				if GameQueueIndices::<T>::contains_key(&game_engine) {
+					// remove element from the waiting queue for Ajuna TEE
+					Self::game_queue(&game_engine).remove_item(&game_hash);
 				}
-			}
 
-			// #TODO[MUST_HAVE, VEC_REMOVE] remove a game from the queue.
+				// Emit an event.
+				Self::deposit_event(Event::GameStateDropped(game_hash));
+			}
 
 			Ok(())
 		}
 
 		/// Acknowledge game will remove from queue and set state to accepted.
-		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+			3 + 2 * games.len().min(MAX_ACK_GAMES) as Weight,
+			1 + 2 * games.len().min(MAX_ACK_GAMES) as Weight,
+		))]
 		pub fn ack_game(
 			origin: OriginFor<T>,
 			cluster: GameEngine,
 			games: Vec<T::Hash>,
 		) -> DispatchResult {
-			// #TODO[MUST_HAVE, SIGNATURE_CHECK] check that it's signed by a registred AjunaTEE.
 			let who = ensure_signed(origin)?;
+			Self::ensure_tee(&who, &cluster)?;
 
 			// only up to 100 games allowed to acknowledge in one batch.
-			if games.len() > 100 {
+			if games.len() > MAX_ACK_GAMES {
 				return Err(Error::<T>::AckToMany)?
 			}
 
-			// #TODO[OPTIMIZATION, STORAGE] optimize storage to use a ringbuffer instead of the vector to avoid to big elements beeing read and written down to the queue.
-
 			// retrieve game queue for asked cluster
-			ensure!(GameQueues::<T>::contains_key(&cluster), Error::<T>::NoGameQueue);
-			let mut game_queue = Self::game_queues(&cluster);
+			ensure!(GameQueueIndices::<T>::contains_key(&cluster), Error::<T>::NoGameQueue);
+			let mut game_queue = Self::game_queue(&cluster);
 
 			let mut games_count = 0;
 			for game_hash_tee in games.iter() {
-				let game_hash = game_queue.peek();
+				let game_hash = game_queue.peek_front();
 
 				// check if peeked game matches acknowledge
-				if game_hash == Some(game_hash_tee) {
-					// dequeue game hash from waiting queue cluster
-					let _ = game_queue.dequeue();
-
-					// insert changed queue back
-					<GameQueues<T>>::insert(cluster.clone(), game_queue.clone());
-
+				if game_hash.as_ref() == Some(game_hash_tee) {
 					// retrieve game entry to change state
 					let mut game_entry = Self::game_registry(game_hash_tee.clone());
+					Self::change_state(&mut game_entry, GameState::Accepted)?;
 
-					game_entry.state_change[1] = <frame_system::Pallet<T>>::block_number();
-					game_entry.game_state = GameState::Accepted;
+					// the acknowledging Ajuna TEE runs the game
+					game_entry.tee_id = Some(who.clone());
+
+					// dequeue game hash from waiting queue cluster, committed on return
+					let _ = game_queue.pop_front();
 
 					// insert changed game entry back
 					<GameRegistry<T>>::insert(game_hash_tee, game_entry);
@@ -449,16 +634,14 @@ pub mod pallet {
 		/// Drop game will remove the game from the queue and the registry.
 		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
 		pub fn ready_game(origin: OriginFor<T>, game_hash: T::Hash) -> DispatchResult {
-			// #TODO[MUST_HAVE, SIGNATURE_CHECK] check that it's signed by a registred AjunaTEE.
 			let who = ensure_signed(origin)?;
 
 			// retrieve game entry
 			ensure!(GameRegistry::<T>::contains_key(&game_hash), Error::<T>::NoGameEntry);
 			let mut game_entry = Self::game_registry(&game_hash);
-
-			game_entry.tee_id = Some(who.clone());
-			game_entry.state_change[2] = <frame_system::Pallet<T>>::block_number();
-			game_entry.game_state = GameState::Running;
+			Self::ensure_tee(&who, &game_entry.game_engine)?;
+			Self::change_state(&mut game_entry, GameState::Running)?;
+			ensure!(game_entry.tee_id == Some(who.clone()), Error::<T>::WrongTee);
 
 			// insert changed game entry back
 			<GameRegistry<T>>::insert(game_hash, game_entry.clone());
@@ -476,28 +659,260 @@ pub mod pallet {
 			game_hash: T::Hash,
 			winner: T::AccountId,
 		) -> DispatchResult {
-			// #TODO[MUST_HAVE, SIGNATURE_CHECK] check that it's signed by a registred AjunaTEE.
 			let who = ensure_signed(origin)?;
 
 			// retrieve game entry
 			ensure!(GameRegistry::<T>::contains_key(&game_hash), Error::<T>::NoGameEntry);
 			let mut game_entry = Self::game_registry(&game_hash);
-
-			game_entry.state_change[3] = <frame_system::Pallet<T>>::block_number();
-			game_entry.game_state = GameState::Finished(winner.clone());
+			Self::ensure_tee(&who, &game_entry.game_engine)?;
+			Self::change_state(&mut game_entry, GameState::Finished(winner.clone()))?;
+			ensure!(game_entry.tee_id == Some(who), Error::<T>::WrongTee);
 
 			// insert changed game entry back
 			<GameRegistry<T>>::insert(game_hash, game_entry.clone());
 
+			// Update ratings, the winner has won against every other player.
+			if game_entry.players.contains(&winner) {
+				for player in game_entry.players.iter().filter(|p| **p != winner) {
+					T::MatchMaker::report_result(winner.clone(), player.clone(), MatchResult::Won);
+				}
+			}
+
+			// Return the deposits of all players.
+			for player in game_entry.players {
+				T::MatchMaker::release_deposit(player);
+			}
+
 			// Emit an event.
 			Self::deposit_event(Event::GameStateFinished(game_hash, winner));
 
 			Ok(())
 		}
+
+		/// Register a game engine version with its metadata and rules, its players are
+		/// matched in a game mode of the matchmaker no other game engine uses.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,3))]
+		pub fn register_engine(
+			origin: OriginFor<T>,
+			game_engine: GameEngine,
+			game_mode: GameModeId,
+			metadata: Vec<u8>,
+			rules: Vec<GameRule<GameRuleType>>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			Self::do_register_engine(game_engine.clone(), game_mode, metadata, rules)?;
+
+			// Emit an event.
+			Self::deposit_event(Event::EngineRegistered(game_engine, game_mode));
+
+			Ok(())
+		}
+
+		/// Deprecate a game engine, players can't queue for it anymore.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		pub fn deprecate_engine(origin: OriginFor<T>, game_engine: GameEngine) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<GameEngines<T>>::try_mutate(&game_engine, |engine| match engine {
+				Some(engine) if engine.status == EngineStatus::Active => {
+					engine.status = EngineStatus::Deprecated;
+					Ok(())
+				},
+				Some(_) => Err(Error::<T>::EngineNotActive),
+				None => Err(Error::<T>::UnknownEngine),
+			})?;
+
+			// Emit an event.
+			Self::deposit_event(Event::EngineDeprecated(game_engine));
+
+			Ok(())
+		}
+
+		/// Retire a game engine, players still queued or in a pending match for it are
+		/// removed and its game mode is free to use for another game engine.
+		#[pallet::weight(
+			10_000 +
+				T::DbWeight::get().reads_writes(2, 3) +
+				T::MatchMaker::all_empty_queue_weight()
+		)]
+		pub fn retire_engine(origin: OriginFor<T>, game_engine: GameEngine) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			let game_mode = <GameEngines<T>>::try_mutate(
+				&game_engine,
+				|engine| -> Result<GameModeId, DispatchError> {
+					match engine {
+						Some(engine) if engine.status != EngineStatus::Retired => {
+							// Return players queued for the game engine, games already created
+							// are played out.
+							T::MatchMaker::all_empty_queue(engine.game_mode)?;
+							engine.status = EngineStatus::Retired;
+							Ok(engine.game_mode)
+						},
+						Some(_) => Err(Error::<T>::EngineRetired)?,
+						None => Err(Error::<T>::UnknownEngine)?,
+					}
+				},
+			)?;
+			<ModeEngines<T>>::remove(game_mode);
+
+			// Emit an event.
+			Self::deposit_event(Event::EngineRetired(game_engine));
+
+			Ok(())
+		}
+
+		/// Register an Ajuna TEE enclave with the hash of its attestation report and the game
+		/// engines it serves, registering it again updates it.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn register_tee(
+			origin: OriginFor<T>,
+			tee: T::AccountId,
+			attestation: T::Hash,
+			clusters: Vec<GameEngine>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			ensure!(clusters.len() <= MAX_TEE_CLUSTERS, Error::<T>::TooManyClusters);
+
+			let enclave = TeeEnclave { attestation, clusters, active: true };
+			<TeeEnclaves<T>>::insert(&tee, enclave);
+
+			// Emit an event.
+			Self::deposit_event(Event::TeeRegistered(tee, attestation));
+
+			Ok(())
+		}
+
+		/// Activate or deactivate a registered Ajuna TEE enclave.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		pub fn set_tee_active(
+			origin: OriginFor<T>,
+			tee: T::AccountId,
+			active: bool,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<TeeEnclaves<T>>::try_mutate(&tee, |enclave| match enclave {
+				Some(enclave) => {
+					enclave.active = active;
+					Ok(())
+				},
+				None => Err(Error::<T>::NotRegisteredTee),
+			})?;
+
+			// Emit an event.
+			Self::deposit_event(Event::TeeActiveSet(tee, active));
+
+			Ok(())
+		}
+
+		/// Remove a registered Ajuna TEE enclave.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		pub fn deregister_tee(origin: OriginFor<T>, tee: T::AccountId) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			ensure!(<TeeEnclaves<T>>::contains_key(&tee), Error::<T>::NotRegisteredTee);
+			<TeeEnclaves<T>>::remove(&tee);
+
+			// Emit an event.
+			Self::deposit_event(Event::TeeDeregistered(tee));
+
+			Ok(())
+		}
 	}
 }
 
+/// Game entry as stored in the registry.
+pub type GameEntryOf<T> = GameEntry<
+	<T as frame_system::Config>::Hash,
+	<T as frame_system::Config>::AccountId,
+	GameEngine,
+	GameState<<T as frame_system::Config>::AccountId>,
+	<T as frame_system::Config>::BlockNumber,
+>;
+
+/// Waiting queue of the games of a game engine.
+pub type GameQueue<T> = StorageDeque<
+	GameEngine,
+	<T as frame_system::Config>::Hash,
+	GameQueueIndices<T>,
+	GameQueueItems<T>,
+>;
+
 impl<T: Config> Pallet<T> {
+	/// Return the waiting queue of a game engine, changes are committed once it is dropped.
+	pub fn game_queue(game_engine: &GameEngine) -> GameQueue<T> {
+		GameQueue::<T>::new(game_engine.clone(), MAX_QUEUE_SIZE.into())
+	}
+
+	/// Return whether the waiting queue of a game engine is full, reading only its bounds.
+	fn is_game_queue_full(game_engine: &GameEngine) -> bool {
+		let (start, end) = Self::game_queue_indices(game_engine);
+		end.wrapping_sub(start) >= MAX_QUEUE_SIZE.into()
+	}
+
+	/// Add a game engine to the catalogue, along with its rules.
+	fn do_register_engine(
+		game_engine: GameEngine,
+		game_mode: GameModeId,
+		metadata: Vec<u8>,
+		rules: Vec<GameRule<GameRuleType>>,
+	) -> DispatchResult {
+		ensure!(!<GameEngines<T>>::contains_key(&game_engine), Error::<T>::EngineAlreadyRegistered);
+		ensure!(!<ModeEngines<T>>::contains_key(game_mode), Error::<T>::GameModeInUse);
+		ensure!(metadata.len() <= MAX_ENGINE_METADATA, Error::<T>::MetadataTooLong);
+		for rule in rules.iter() {
+			if let GameRuleType::PlayersPerGame([min, max]) = rule.game_rule_type {
+				// a rule no match of the game mode can meet would drop all of its players
+				let (mode_min, mode_max) = T::MatchMaker::match_size(game_mode)?;
+				ensure!(
+					min <= max && min <= mode_max && max >= mode_min,
+					Error::<T>::InvalidGameRule
+				);
+			}
+		}
+
+		let engine = EngineInfo { game_mode, status: EngineStatus::Active, metadata };
+		<GameEngines<T>>::insert(&game_engine, engine);
+		<ModeEngines<T>>::insert(game_mode, &game_engine);
+		<GameRequirments<T>>::insert(&game_engine, rules);
+		Ok(())
+	}
+
+	/// Ensure an account is a registered and active Ajuna TEE enclave serving a game engine.
+	fn ensure_tee(who: &T::AccountId, game_engine: &GameEngine) -> DispatchResult {
+		let enclave = Self::tee_enclaves(who).ok_or(Error::<T>::NotRegisteredTee)?;
+		ensure!(enclave.active, Error::<T>::InactiveTee);
+		ensure!(enclave.clusters.contains(game_engine), Error::<T>::WrongTeeCluster);
+		Ok(())
+	}
+
+	/// Move a game entry to the next state of its lifecycle and record the block of the change.
+	fn change_state(
+		game_entry: &mut GameEntryOf<T>,
+		game_state: GameState<T::AccountId>,
+	) -> DispatchResult {
+		let index = match (&game_entry.game_state, &game_state) {
+			(GameState::Waiting, GameState::Accepted) => 1,
+			(GameState::Accepted, GameState::Running) => 2,
+			(GameState::Running, GameState::Finished(_)) => 3,
+			// a game ends when it is dropped as well
+			(GameState::Waiting, GameState::Dropped) |
+			(GameState::Accepted, GameState::Dropped) |
+			(GameState::Running, GameState::Dropped) => 3,
+			(_, GameState::Accepted) => return Err(Error::<T>::GameNotWaiting)?,
+			(_, GameState::Running) => return Err(Error::<T>::GameNotAccepted)?,
+			(_, GameState::Finished(_)) => return Err(Error::<T>::GameNotRunning)?,
+			_ => return Err(Error::<T>::GameEnded)?,
+		};
+
+		game_entry.state_change[index] = <frame_system::Pallet<T>>::block_number();
+		game_entry.game_state = game_state;
+		Ok(())
+	}
+
 	/// Update nonce once used.
 	fn encode_and_update_nonce() -> Vec<u8> {
 		let nonce = <Nonce<T>>::get();
@@ -517,29 +932,49 @@ impl<T: Config> Pallet<T> {
 	fn queue_game(game_engine: GameEngine, players: Vec<T::AccountId>) -> DispatchResult {
 		// check if requirements for this game are meet, for all the players.
 		let game_rules = Self::game_requirements(&game_engine);
-		for _game_rule in game_rules.iter() {
-			// #TODO[MUST_HAVE, REQUIRMENTS_CHECK] check if game engine requirments are meet for the players.
+		for game_rule in game_rules.iter() {
+			let failed = Self::failed_rule(&game_rule.game_rule_type, &players);
+			if failed.is_empty() {
+				continue
+			}
+
+			// players failing a requirement would fail it again, so only the others go back
+			// to the queue, a match failing a rule of the game itself is dropped as a whole
+			Self::requeue_players(&players, &failed);
+
+			// Emit an event.
+			Self::deposit_event(Event::GameRuleFailed(
+				game_engine,
+				game_rule.game_rule_type.clone(),
+				failed,
+			));
+
+			return Err(Error::<T>::GameRuleFailed)?
 		}
 
 		// #TODO[MUST_HAVE, HAS_A_PLAYER] must have at least one player.
 
-		// create new game entry with corresponding informations
-		let game_entry = Self::create_game_entry(game_engine.clone(), players);
+		// the matchmaker holds players back while the game queue is full, see `has_capacity`,
+		// a match still arriving is dropped
+		if Self::is_game_queue_full(&game_engine) {
+			Self::requeue_players(&players, &players);
 
-		// insert game entry into registry.
-		<GameRegistry<T>>::insert(game_entry.id.clone(), game_entry.clone());
+			// Emit an event.
+			Self::deposit_event(Event::GameQueueFull(game_engine));
 
-		// retrieve game queue for asked cluster
-		let mut game_queue = Queue::new(MAX_QUEUE_SIZE.into());
-		if GameQueues::<T>::contains_key(&game_engine) {
-			game_queue = Self::game_queues(&game_engine);
+			return Err(Error::<T>::GameQueueFull)?
 		}
 
-		// enqueue new game id
-		game_queue.enqueue(game_entry.id.clone());
+		// create new game entry with corresponding informations
+		let game_entry = Self::create_game_entry(game_engine.clone(), players);
+
+		// enqueue new game id into waiting queue for Ajuna TEE, the queue has room left
+		Self::game_queue(&game_engine)
+			.push_back(game_entry.id.clone())
+			.map_err(|_| Error::<T>::GameQueueFull)?;
 
-		// insert into waiting queue for Ajuna TEE
-		<GameQueues<T>>::insert(&game_engine, game_queue);
+		// insert game entry into registry.
+		<GameRegistry<T>>::insert(game_entry.id.clone(), game_entry.clone());
 
 		// Emit an event.
 		Self::deposit_event(Event::GameQueued(game_engine, game_entry.id));
@@ -548,11 +983,56 @@ impl<T: Config> Pallet<T> {
 		Ok(())
 	}
 
+	/// Send matched players back to the front of the matchmaker queue, where they keep their
+	/// wait, the players left out and their parties get their deposits back instead.
+	fn requeue_players(players: &[T::AccountId], left_out: &[T::AccountId]) {
+		// the deposits of the match are returned, queueing again reserves new ones
+		for player in players.iter() {
+			T::MatchMaker::release_deposit(player.clone());
+		}
+
+		// parties are queued by their leader, along with all of their members
+		let left_out: Vec<T::AccountId> = left_out
+			.iter()
+			.map(|player| T::MatchMaker::party_leader(player.clone()))
+			.collect();
+		// each unit goes in front of the queue, in reverse they keep their order
+		for player in players.iter().rev() {
+			let leader = T::MatchMaker::party_leader(player.clone());
+			if leader == *player &&
+				!left_out.contains(&leader) &&
+				T::MatchMaker::requeue(player.clone()).is_err()
+			{
+				Self::deposit_event(Event::RequeueFailed(player.clone()));
+			}
+		}
+	}
+
+	/// Return the players failing a game rule, all of them if the game itself fails it.
+	fn failed_rule(rule: &GameRuleType, players: &[T::AccountId]) -> Vec<T::AccountId> {
+		if let GameRuleType::PlayersPerGame([min, max]) = rule {
+			if players.len() < *min as usize || players.len() > *max as usize {
+				return players.to_vec()
+			}
+			return Vec::new()
+		}
+
+		players
+			.iter()
+			.filter(|player| match rule {
+				GameRuleType::MinimumRating(rating) =>
+					T::MatchMaker::rating((*player).clone()) < *rating,
+				GameRuleType::RequiredBalance(balance) =>
+					T::Currency::free_balance(player) < (*balance).saturated_into(),
+				GameRuleType::OwnedAsset(asset) => !T::Assets::owns(player, asset),
+				_ => false,
+			})
+			.cloned()
+			.collect()
+	}
+
 	/// Generate a new game entry in waiting state.
-	fn create_game_entry(
-		game_engine: GameEngine,
-		players: Vec<T::AccountId>,
-	) -> GameEntry<T::Hash, T::AccountId, GameEngine, GameState<T::AccountId>, T::BlockNumber> {
+	fn create_game_entry(game_engine: GameEngine, players: Vec<T::AccountId>) -> GameEntryOf<T> {
 		// get a random hash as game id
 		let game_id = Self::generate_random_hash(&GAMEREGISTRY_ID, players[0].clone());
 
@@ -573,3 +1053,39 @@ impl<T: Config> Pallet<T> {
 		return game_entry
 	}
 }
+
+impl<T: Config> OnMatched<T::AccountId> for Pallet<T> {
+	fn on_matched(
+		mode: GameModeId,
+		_match_id: MatchId,
+		players: Vec<T::AccountId>,
+	) -> (bool, Weight) {
+		// matches of game modes without a game engine are played elsewhere
+		let game_engine = match Self::engine_of_mode(mode) {
+			Some(game_engine) => game_engine,
+			None => return (false, T::DbWeight::get().reads(1)),
+		};
+		let db = T::DbWeight::get();
+		let rules = Self::game_requirements(&game_engine).len() as Weight;
+		let players_count = players.len() as Weight;
+
+		// Create new game, players of a game that fails are requeued or released by it
+		let _game_id = Self::queue_game(game_engine, players);
+
+		// every rule is checked for every player, a failed game requeues each of them
+		// weights need to be adjusted
+		let weight = db
+			.reads_writes(6, 5)
+			.saturating_add(db.reads(rules.saturating_mul(players_count)))
+			.saturating_add(db.reads_writes(6, 5).saturating_mul(players_count));
+		(true, weight)
+	}
+
+	fn has_capacity(mode: GameModeId) -> bool {
+		// players wait in the matchmaker queue, until the Ajuna TEE caught up with the games
+		match Self::engine_of_mode(mode) {
+			Some(game_engine) => !Self::is_game_queue_full(&game_engine),
+			None => true,
+		}
+	}
+}
@@ -0,0 +1,57 @@
+use super::*;
+use frame_support::{
+	storage::migration::{storage_key_iter, take_storage_value},
+	traits::{GetStorageVersion, PalletInfoAccess, StorageVersion},
+	Identity,
+};
+
+pub mod v1 {
+	use super::*;
+
+	/// Queue of games before version 1, stored as a whole under its game engine.
+	#[derive(Decode)]
+	struct OldQueue<Hash> {
+		_max_size: u32,
+		queue: Vec<Hash>,
+	}
+
+	/// Migrate the game queues to version 1.
+	///
+	/// Before version 1 the queued games of a game engine were stored as one vector in
+	/// `GameQueues`, they are pushed in order into the deque of the game engine and the old
+	/// entries are removed.
+	pub fn migrate<T: Config>() -> Weight {
+		let on_chain_version = Pallet::<T>::on_chain_storage_version();
+		if on_chain_version >= 1 {
+			return T::DbWeight::get().reads(1)
+		}
+
+		let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+		let old: Vec<(GameEngine, OldQueue<T::Hash>)> =
+			storage_key_iter::<GameEngine, OldQueue<T::Hash>, Identity>(pallet, b"GameQueues")
+				.collect();
+
+		let mut reads: Weight = 1 + old.len() as Weight;
+		let mut writes: Weight = 1;
+		for (game_engine, old_queue) in old {
+			let _ = take_storage_value::<OldQueue<T::Hash>>(
+				pallet,
+				b"GameQueues",
+				&game_engine.encode(),
+			);
+			let mut game_queue = Pallet::<T>::game_queue(&game_engine);
+			for game_hash in old_queue.queue {
+				// the old queues had the same maximum size, so every game fits
+				let _ = game_queue.push_back(game_hash);
+				writes += 1;
+			}
+			reads += 1;
+			writes += 2;
+		}
+
+		StorageVersion::new(1).put::<Pallet<T>>();
+
+		// weights need to be adjusted
+		T::DbWeight::get().reads_writes(reads, writes)
+	}
+}
@@ -1,5 +1,6 @@
 use super::*;
 use crate as pallet_gameregistry;
+use pallet_matchmaker::{GameModeSettings, MatchingType};
 
 use sp_core::H256;
 
@@ -29,6 +30,7 @@ construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Event<T>},
 		Scheduler: pallet_scheduler::{Pallet, Call, Config, Storage, Event<T>},
 		MatchMaker: pallet_matchmaker::{Pallet, Call, Storage, Event<T>},
 		Registry: pallet_gameregistry::{Pallet, Call, Config<T>, Storage, Event<T>},
@@ -60,7 +62,7 @@ impl frame_system::Config for Test {
 	type BlockHashCount = BlockHashCount;
 	type Version = ();
 	type PalletInfo = PalletInfo;
-	type AccountData = ();
+	type AccountData = pallet_balances::AccountData<u64>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
@@ -85,15 +87,67 @@ impl pallet_scheduler::Config for Test {
 }
 
 parameter_types! {
-	pub const AmountPlayers: u8 = 2;
+	pub const ExistentialDeposit: u64 = 1;
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ();
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type Balance = u64;
+	type Event = Event;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub GameModes: Vec<GameModeSettings<u64>> =
+		vec![GameModeSettings { min_players: 2, max_players: 2, fill_timeout: 0 }];
 	pub const AmountBrackets: u8 = 3;
+	pub const DefaultRating: u16 = 1500;
+	pub const RatingFactor: u16 = 32;
+	pub BracketThresholds: Vec<u16> = vec![1400, 1600];
+	pub const DefaultMatchingType: MatchingType = MatchingType::Simple;
+	pub const WideningPeriod: u64 = 10;
+	pub const QueueTimeToLive: u64 = 50;
+	pub const MaxExpiriesPerBlock: u32 = 2;
+	pub const MaxQueueSize: u16 = 8;
+	pub const MaxPartySize: u32 = 3;
+	pub const AcceptPeriod: u64 = 0;
+	pub const DeclinePenalty: u16 = 16;
+	pub const QueueDeposit: u64 = 0;
+	pub const MaxRecentOpponents: u32 = 4;
+	pub const RematchWindow: u64 = 20;
+	pub const MaxMatchesPerBlock: u32 = 10;
 }
 
 /// Used for matchmaking in pallets/connectfour.
 impl pallet_matchmaker::Config for Test {
 	type Event = Event;
-	type AmountPlayers = AmountPlayers;
+	type GameModes = GameModes;
 	type AmountBrackets = AmountBrackets;
+	type DefaultRating = DefaultRating;
+	type RatingFactor = RatingFactor;
+	type BracketThresholds = BracketThresholds;
+	type DefaultMatchingType = DefaultMatchingType;
+	type WideningPeriod = WideningPeriod;
+	type QueueTimeToLive = QueueTimeToLive;
+	type MaxExpiriesPerBlock = MaxExpiriesPerBlock;
+	type MaxQueueSize = MaxQueueSize;
+	type MaxPartySize = MaxPartySize;
+	type AcceptPeriod = AcceptPeriod;
+	type DeclinePenalty = DeclinePenalty;
+	type Currency = Balances;
+	type QueueDeposit = QueueDeposit;
+	type MaxRecentOpponents = MaxRecentOpponents;
+	type RematchWindow = RematchWindow;
+	type PlayerAttributes = ();
+	type MatchFilter = ();
+	type MaxMatchesPerBlock = MaxMatchesPerBlock;
+	type OnMatched = Registry;
+	type AdminOrigin = frame_system::EnsureRoot<u64>;
 }
 
 impl pallet_gameregistry::Config for Test {
@@ -103,6 +157,17 @@ impl pallet_gameregistry::Config for Test {
 	type Scheduler = Scheduler;
 	type PalletsOrigin = OriginCaller;
 	type MatchMaker = MatchMaker;
+	type Currency = Balances;
+	type Assets = TestAssets;
+	type AdminOrigin = EnsureRoot<u64>;
+}
+
+/// Only account 1 owns an asset, the one with all bytes set to 1.
+pub struct TestAssets;
+impl AssetOwnership<u64> for TestAssets {
+	fn owns(account: &u64, asset: &[u8; 16]) -> bool {
+		*account == 1 && *asset == [1; 16]
+	}
 }
 
 /// Build genesis storage according to the mock runtime.
@@ -111,7 +176,10 @@ pub(crate) fn new_test_ext() -> sp_io::TestExternalities {
 	let t = GenesisConfig {
 		system: Default::default(),
 		scheduler: Default::default(),
-		registry: Default::default(),
+		registry: pallet_gameregistry::GenesisConfig {
+			founder_key: Default::default(),
+			engines: vec![(GameEngine { id: 1, version: 1 }, 0, b"Connect Four".to_vec(), vec![])],
+		},
 	}
 	.build_storage()
 	.unwrap();
@@ -129,6 +197,7 @@ pub fn run_to_block(n: u64) {
 			// mock on_finalize
 			System::on_finalize(System::block_number());
 			Scheduler::on_finalize(System::block_number());
+			MatchMaker::on_finalize(System::block_number());
 			Registry::on_finalize(System::block_number());
 		}
 
@@ -137,6 +206,7 @@ pub fn run_to_block(n: u64) {
 		// mock on_initialize
 		System::on_initialize(System::block_number());
 		Scheduler::on_initialize(System::block_number());
+		MatchMaker::on_initialize(System::block_number());
 		Registry::on_initialize(System::block_number());
 	}
 }
@@ -1,7 +1,13 @@
 use super::*;
 
-use crate::{mock::*, Error};
-use frame_support::{assert_noop, assert_ok};
+use crate::{migrations, mock::*, Error};
+use frame_support::{
+	assert_noop, assert_ok,
+	error::BadOrigin,
+	storage::migration::{get_storage_value, put_storage_value},
+	traits::{GetStorageVersion, StorageVersion},
+};
+use sp_core::H256;
 
 #[test]
 fn it_works_for_default_value() {
@@ -41,28 +47,34 @@ fn regsitry_test() {
 		// start from block 100
 		run_to_block(current_block);
 
-		let queue_test1 = Registry::game_queues(&game_engine1);
-		assert_eq!(queue_test1.length(), 0);
+		// register Ajuna TEE serving the game engine
+		assert_ok!(Registry::register_tee(
+			Origin::root(),
+			tee,
+			H256::repeat_byte(1),
+			vec![game_engine1.clone()]
+		));
+
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
 
 		// queue up matchmaker first player
-		assert_ok!(Registry::queue(Origin::signed(player1)));
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
 
 		run_next_block();
 		current_block = current_block + 1;
 		assert_eq!(System::block_number(), current_block);
 
 		// queue up matchmaker second player
-		assert_ok!(Registry::queue(Origin::signed(player2)));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
 
 		run_next_block();
 		current_block = current_block + 1;
 		assert_eq!(System::block_number(), current_block);
 
 		// check if we have something queued
-		let queue_test2 = Registry::game_queues(&game_engine1);
-		assert_eq!(queue_test2.length(), 1);
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 1);
 
-		let game_hash = queue_test2.peek().unwrap();
+		let game_hash = Registry::game_queue(&game_engine1).peek_front().unwrap();
 		let mut games = Vec::new();
 		games.push(game_hash.clone());
 
@@ -74,8 +86,7 @@ fn regsitry_test() {
 		assert_ok!(Registry::ack_game(Origin::signed(tee), game_engine1.clone(), games));
 
 		// check if we have something queued
-		let queue_test3 = Registry::game_queues(&game_engine1);
-		assert_eq!(queue_test3.length(), 0);
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
 
 		run_next_block();
 
@@ -112,3 +123,488 @@ fn regsitry_test() {
 		assert_eq!(game_entry5.game_state, GameState::None);
 	});
 }
+
+#[test]
+fn leave_queue_test() {
+	new_test_ext().execute_with(|| {
+		let player1: u64 = 1u64;
+		let player2: u64 = 2u64;
+
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+
+		// start from block 100
+		run_to_block(100);
+
+		// can't leave without being queued
+		assert_noop!(
+			Registry::leave_queue(Origin::signed(player1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up first player and leave again
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::leave_queue(Origin::signed(player1)));
+		assert_noop!(
+			Registry::leave_queue(Origin::signed(player1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up second player, no game as first player left
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
+
+		run_next_block();
+
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+	});
+}
+
+#[test]
+fn tee_registry_test() {
+	new_test_ext().execute_with(|| {
+		let player1: u64 = 1u64;
+		let player2: u64 = 2u64;
+
+		let tee: u64 = 7u64;
+		let other_tee: u64 = 8u64;
+
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+		let game_engine2: GameEngine = GameEngine { id: 2, version: 1 };
+
+		run_to_block(100);
+
+		// only the admin origin registers Ajuna TEEs
+		assert_noop!(
+			Registry::register_tee(Origin::signed(tee), tee, H256::zero(), vec![]),
+			BadOrigin
+		);
+		assert_noop!(
+			Registry::register_tee(
+				Origin::root(),
+				tee,
+				H256::zero(),
+				vec![game_engine1.clone(); 17]
+			),
+			Error::<Test>::TooManyClusters
+		);
+		assert_noop!(
+			Registry::set_tee_active(Origin::root(), tee, false),
+			Error::<Test>::NotRegisteredTee
+		);
+		assert_ok!(Registry::register_tee(
+			Origin::root(),
+			tee,
+			H256::repeat_byte(1),
+			vec![game_engine1.clone()]
+		));
+		assert_ok!(Registry::register_tee(
+			Origin::root(),
+			other_tee,
+			H256::repeat_byte(2),
+			vec![game_engine2.clone()]
+		));
+
+		// queue up a game
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
+		run_next_block();
+		let game_hash = Registry::game_queue(&game_engine1).peek_front().unwrap();
+
+		// unregistered accounts and Ajuna TEEs of other game engines are rejected
+		assert_noop!(
+			Registry::ack_game(Origin::signed(player1), game_engine1.clone(), vec![game_hash]),
+			Error::<Test>::NotRegisteredTee
+		);
+		assert_noop!(
+			Registry::ack_game(Origin::signed(other_tee), game_engine1.clone(), vec![game_hash]),
+			Error::<Test>::WrongTeeCluster
+		);
+		assert_noop!(
+			Registry::drop_game(Origin::signed(other_tee), game_hash, game_engine2.clone()),
+			Error::<Test>::WrongTeeCluster
+		);
+
+		// deactivated Ajuna TEEs are rejected until they are activated again
+		assert_ok!(Registry::set_tee_active(Origin::root(), tee, false));
+		assert_noop!(
+			Registry::ack_game(Origin::signed(tee), game_engine1.clone(), vec![game_hash]),
+			Error::<Test>::InactiveTee
+		);
+		assert_ok!(Registry::set_tee_active(Origin::root(), tee, true));
+		assert_ok!(Registry::ack_game(Origin::signed(tee), game_engine1.clone(), vec![game_hash]));
+
+		assert_noop!(
+			Registry::ready_game(Origin::signed(other_tee), game_hash),
+			Error::<Test>::WrongTeeCluster
+		);
+		assert_ok!(Registry::ready_game(Origin::signed(tee), game_hash));
+
+		// deregistered Ajuna TEEs are rejected
+		assert_ok!(Registry::deregister_tee(Origin::root(), tee));
+		assert_noop!(
+			Registry::finish_game(Origin::signed(tee), game_hash, player1),
+			Error::<Test>::NotRegisteredTee
+		);
+		assert_noop!(
+			Registry::deregister_tee(Origin::root(), tee),
+			Error::<Test>::NotRegisteredTee
+		);
+	});
+}
+
+#[test]
+fn game_state_test() {
+	new_test_ext().execute_with(|| {
+		let player1: u64 = 1u64;
+		let player2: u64 = 2u64;
+
+		let tee: u64 = 7u64;
+		let other_tee: u64 = 9u64;
+
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+
+		run_to_block(100);
+
+		for account in [tee, other_tee].iter() {
+			assert_ok!(Registry::register_tee(
+				Origin::root(),
+				*account,
+				H256::repeat_byte(1),
+				vec![game_engine1.clone()]
+			));
+		}
+
+		// queue up a game
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
+		run_next_block();
+		let game_hash = Registry::game_queue(&game_engine1).peek_front().unwrap();
+
+		// a waiting game can't start or finish
+		assert_noop!(
+			Registry::ready_game(Origin::signed(tee), game_hash),
+			Error::<Test>::GameNotAccepted
+		);
+		assert_ok!(Registry::ack_game(Origin::signed(tee), game_engine1.clone(), vec![game_hash]));
+		assert_eq!(Registry::game_registry(&game_hash).tee_id, Some(tee));
+		assert_noop!(
+			Registry::finish_game(Origin::signed(tee), game_hash, player1),
+			Error::<Test>::GameNotRunning
+		);
+
+		// only the acknowledging Ajuna TEE runs the game
+		assert_noop!(
+			Registry::ready_game(Origin::signed(other_tee), game_hash),
+			Error::<Test>::WrongTee
+		);
+		assert_ok!(Registry::ready_game(Origin::signed(tee), game_hash));
+		assert_noop!(
+			Registry::ready_game(Origin::signed(tee), game_hash),
+			Error::<Test>::GameNotAccepted
+		);
+		assert_noop!(
+			Registry::finish_game(Origin::signed(other_tee), game_hash, player1),
+			Error::<Test>::WrongTee
+		);
+		assert_ok!(Registry::finish_game(Origin::signed(tee), game_hash, player1));
+		assert_noop!(
+			Registry::finish_game(Origin::signed(tee), game_hash, player2),
+			Error::<Test>::GameNotRunning
+		);
+
+		// a game dropped before it finished stays dropped
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
+		run_next_block();
+		let game_hash = Registry::game_queue(&game_engine1).peek_front().unwrap();
+		assert_ok!(Registry::drop_game(Origin::signed(tee), game_hash, game_engine1.clone()));
+		assert_eq!(Registry::game_registry(&game_hash).game_state, GameState::Dropped);
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert_noop!(
+			Registry::ack_game(Origin::signed(tee), game_engine1.clone(), vec![game_hash]),
+			Error::<Test>::AckFail
+		);
+
+		// dropping an ended game removes it from the registry
+		assert_ok!(Registry::drop_game(Origin::signed(tee), game_hash, game_engine1.clone()));
+		assert_eq!(Registry::game_registry(&game_hash).game_state, GameState::None);
+	});
+}
+
+#[test]
+fn engine_catalogue_test() {
+	new_test_ext().execute_with(|| {
+		let player1: u64 = 1u64;
+		let player2: u64 = 2u64;
+
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+		let game_engine2: GameEngine = GameEngine { id: 1, version: 2 };
+
+		// start from block 100
+		run_to_block(100);
+
+		// game engine of the genesis config
+		assert_eq!(Registry::engine_of_mode(0), Some(game_engine1.clone()));
+		assert_eq!(Registry::game_engines(&game_engine1).unwrap().status, EngineStatus::Active);
+
+		// only the admin manages game engines
+		assert_noop!(
+			Registry::register_engine(
+				Origin::signed(player1),
+				game_engine2.clone(),
+				1,
+				vec![],
+				vec![]
+			),
+			BadOrigin
+		);
+		assert_noop!(
+			Registry::register_engine(Origin::root(), game_engine1.clone(), 1, vec![], vec![]),
+			Error::<Test>::EngineAlreadyRegistered
+		);
+		assert_noop!(
+			Registry::register_engine(Origin::root(), game_engine2.clone(), 0, vec![], vec![]),
+			Error::<Test>::GameModeInUse
+		);
+		assert_noop!(
+			Registry::register_engine(
+				Origin::root(),
+				game_engine2.clone(),
+				1,
+				vec![0; 257],
+				vec![]
+			),
+			Error::<Test>::MetadataTooLong
+		);
+		assert_noop!(
+			Registry::queue(Origin::signed(player1), game_engine2.clone()),
+			Error::<Test>::UnknownEngine
+		);
+
+		// deprecated game engines don't take new players
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::deprecate_engine(Origin::root(), game_engine1.clone()));
+		assert_noop!(
+			Registry::queue(Origin::signed(player2), game_engine1.clone()),
+			Error::<Test>::EngineNotActive
+		);
+		assert_noop!(
+			Registry::deprecate_engine(Origin::root(), game_engine1.clone()),
+			Error::<Test>::EngineNotActive
+		);
+
+		// retired game engines return their queued players and free their game mode
+		assert_ok!(Registry::retire_engine(Origin::root(), game_engine1.clone()));
+		assert_eq!(Registry::engine_of_mode(0), None);
+		assert_noop!(
+			Registry::leave_queue(Origin::signed(player1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+		assert_noop!(
+			Registry::retire_engine(Origin::root(), game_engine1.clone()),
+			Error::<Test>::EngineRetired
+		);
+
+		// game rules have to fit the matches of the game mode
+		let players_rule = GameRule {
+			game_rule_type: GameRuleType::PlayersPerGame([3, 4]),
+			game_rule_info: [0; 16],
+		};
+		assert_noop!(
+			Registry::register_engine(
+				Origin::root(),
+				game_engine2.clone(),
+				0,
+				vec![],
+				vec![players_rule]
+			),
+			Error::<Test>::InvalidGameRule
+		);
+
+		// the new game engine version gets the games of the game mode
+		assert_ok!(Registry::register_engine(
+			Origin::root(),
+			game_engine2.clone(),
+			0,
+			b"Connect Four v2".to_vec(),
+			vec![]
+		));
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine2.clone()));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine2.clone()));
+
+		run_next_block();
+
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert_eq!(Registry::game_queue(&game_engine2).len(), 1);
+		let game_hash = Registry::game_queue(&game_engine2).peek_front().unwrap();
+		assert_eq!(Registry::game_registry(&game_hash).game_engine, game_engine2);
+	});
+}
+
+#[test]
+fn game_rules_test() {
+	new_test_ext().execute_with(|| {
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+
+		let set_rule = |game_rule_type: GameRuleType| {
+			GameRequirments::<Test>::insert(
+				&game_engine1,
+				vec![GameRule { game_rule_type, game_rule_info: [0; 16] }],
+			);
+		};
+		let rule_failed = |game_rule_type: GameRuleType, players: Vec<u64>| {
+			System::events().iter().any(|record| {
+				record.event ==
+					mock::Event::Registry(crate::Event::GameRuleFailed(
+						game_engine1.clone(),
+						game_rule_type.clone(),
+						players.clone(),
+					))
+			})
+		};
+		let left_queue = |player: u64| Registry::leave_queue(Origin::signed(player)).is_ok();
+
+		// start from block 100
+		run_to_block(100);
+
+		// too few players for the game, the whole match leaves the queue
+		set_rule(GameRuleType::PlayersPerGame([3, 4]));
+		assert_ok!(Registry::queue(Origin::signed(1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(2), game_engine1.clone()));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert!(rule_failed(GameRuleType::PlayersPerGame([3, 4]), vec![1, 2]));
+		assert!(!left_queue(1));
+		assert!(!left_queue(2));
+
+		// players below the rating leave the queue
+		set_rule(GameRuleType::MinimumRating(1501));
+		assert_ok!(Registry::queue(Origin::signed(3), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(4), game_engine1.clone()));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert!(rule_failed(GameRuleType::MinimumRating(1501), vec![3, 4]));
+		assert!(!left_queue(3));
+		assert!(!left_queue(4));
+
+		// only the player short of balance leaves the queue
+		set_rule(GameRuleType::RequiredBalance(100));
+		assert_ok!(Balances::set_balance(Origin::root(), 5, 100, 0));
+		assert_ok!(Registry::queue(Origin::signed(5), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(6), game_engine1.clone()));
+		let queued_at = System::block_number();
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert!(rule_failed(GameRuleType::RequiredBalance(100), vec![6]));
+		// the player queued again keeps the block it queued at
+		let (mode, bracket, _) = MatchMaker::key_index(5).unwrap();
+		assert_eq!(MatchMaker::key_value((mode, bracket), 5).queued_at, queued_at);
+		assert!(left_queue(5));
+		assert!(!left_queue(6));
+
+		// only the player owning the asset stays in the queue
+		set_rule(GameRuleType::OwnedAsset([1; 16]));
+		assert_ok!(Registry::queue(Origin::signed(1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(7), game_engine1.clone()));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert!(rule_failed(GameRuleType::OwnedAsset([1; 16]), vec![7]));
+		assert!(left_queue(1));
+		assert!(!left_queue(7));
+
+		// a party member failing a requirement takes its whole party out of the queue
+		set_rule(GameRuleType::RequiredBalance(100));
+		assert_ok!(Balances::set_balance(Origin::root(), 10, 100, 0));
+		assert_ok!(MatchMaker::create_party(Origin::signed(10)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(10), 11));
+		assert_ok!(MatchMaker::join_party(Origin::signed(11), 10));
+		assert_ok!(Registry::queue(Origin::signed(10), game_engine1.clone()));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 0);
+		assert!(rule_failed(GameRuleType::RequiredBalance(100), vec![11]));
+		assert!(!left_queue(10));
+
+		// players meeting all requirements get their game
+		set_rule(GameRuleType::MinimumRating(1500));
+		assert_ok!(Registry::queue(Origin::signed(8), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(9), game_engine1.clone()));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 1);
+	});
+}
+
+#[test]
+fn game_queue_full_test() {
+	new_test_ext().execute_with(|| {
+		let player1: u64 = 1u64;
+		let player2: u64 = 2u64;
+		let player3: u64 = 3u64;
+
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+
+		// start from block 100
+		run_to_block(100);
+
+		// matched players, while the Ajuna TEE falls behind with the games
+		assert_ok!(Registry::queue(Origin::signed(player1), game_engine1.clone()));
+		assert_ok!(Registry::queue(Origin::signed(player2), game_engine1.clone()));
+		{
+			let mut game_queue = Registry::game_queue(&game_engine1);
+			for i in 0..64u8 {
+				assert_ok!(game_queue.push_back(H256::repeat_byte(i)));
+			}
+			assert_eq!(
+				game_queue.push_back(H256::repeat_byte(64)),
+				Err(pallet_matchmaker::DequeError::Full)
+			);
+		}
+
+		// no new players are queued for the game engine
+		assert_noop!(
+			Registry::queue(Origin::signed(player3), game_engine1.clone()),
+			Error::<Test>::GameQueueFull
+		);
+
+		// players are held back in the matchmaker queue before they are matched
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 64);
+		assert!(!System::events().iter().any(|record| record.event ==
+			mock::Event::Registry(crate::Event::GameQueueFull(game_engine1.clone()))));
+		assert_eq!(MatchMaker::queue_position(player1).map(|(_, _, position)| position), Some(0));
+
+		// once the Ajuna TEE caught up, they get their game
+		assert_eq!(Registry::game_queue(&game_engine1).pop_front(), Some(H256::repeat_byte(0)));
+		run_next_block();
+		assert_eq!(Registry::game_queue(&game_engine1).len(), 64);
+		assert_noop!(
+			Registry::leave_queue(Origin::signed(player1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+	});
+}
+
+#[test]
+fn test_game_queue_migration() {
+	new_test_ext().execute_with(|| {
+		// a queue of games before version 1, stored as its maximum size and its games
+		let game_engine1: GameEngine = GameEngine { id: 1, version: 1 };
+		let games = vec![H256::repeat_byte(1), H256::repeat_byte(2)];
+		put_storage_value(b"Registry", b"GameQueues", &game_engine1.encode(), (64u32, &games));
+		StorageVersion::new(0).put::<Registry>();
+
+		migrations::v1::migrate::<Test>();
+
+		assert_eq!(Registry::on_chain_storage_version(), 1);
+		assert_eq!(
+			get_storage_value::<(u32, Vec<H256>)>(
+				b"Registry",
+				b"GameQueues",
+				&game_engine1.encode()
+			),
+			None
+		);
+		let mut game_queue = Registry::game_queue(&game_engine1);
+		assert_eq!(game_queue.len(), 2);
+		assert_eq!(game_queue.pop_front(), Some(games[0]));
+		assert_eq!(game_queue.pop_front(), Some(games[1]));
+	});
+}
@@ -0,0 +1,28 @@
+//! Runtime API definition for the matchmaker pallet.
+//!
+//! Game modes and brackets are `u8`, queue sizes and positions are `u16`, like the
+//! `GameModeId`, `Bracket` and `BufferIndex` types of the pallet.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	pub trait MatchmakerApi<AccountId, BlockNumber> where
+		AccountId: Codec,
+		BlockNumber: Codec,
+	{
+		/// Return the queue size of each bracket of a game mode.
+		fn bracket_sizes(mode: u8) -> Vec<u16>;
+
+		/// Return the total amount of queued players of a game mode.
+		fn total_queued(mode: u8) -> u16;
+
+		/// Return the game mode, bracket and position in the bracket queue of an account, the
+		/// position being the amount of players queued ahead.
+		fn queue_position(account: AccountId) -> Option<(u8, u8, u16)>;
+
+		/// Return the estimated blocks until a queued account is matched.
+		fn estimated_wait(account: AccountId) -> Option<BlockNumber>;
+	}
+}
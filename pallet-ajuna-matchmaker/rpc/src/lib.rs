@@ -0,0 +1,108 @@
+//! RPC interface for the matchmaker pallet.
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{marker::PhantomData, sync::Arc};
+
+pub use pallet_matchmaker_rpc_runtime_api::MatchmakerApi as MatchmakerRuntimeApi;
+
+#[rpc]
+pub trait MatchmakerApi<BlockHash, AccountId, BlockNumber> {
+	/// Return the queue size of each bracket of a game mode.
+	#[rpc(name = "matchmaker_bracketSizes")]
+	fn bracket_sizes(&self, mode: u8, at: Option<BlockHash>) -> Result<Vec<u16>>;
+
+	/// Return the total amount of queued players of a game mode.
+	#[rpc(name = "matchmaker_totalQueued")]
+	fn total_queued(&self, mode: u8, at: Option<BlockHash>) -> Result<u16>;
+
+	/// Return the game mode, bracket and position in the bracket queue of an account.
+	#[rpc(name = "matchmaker_queuePosition")]
+	fn queue_position(
+		&self,
+		account: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<Option<(u8, u8, u16)>>;
+
+	/// Return the estimated blocks until a queued account is matched.
+	#[rpc(name = "matchmaker_estimatedWait")]
+	fn estimated_wait(
+		&self,
+		account: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<Option<BlockNumber>>;
+}
+
+/// A struct that implements the [`MatchmakerApi`].
+pub struct Matchmaker<C, B> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Matchmaker<C, B> {
+	/// Create new `Matchmaker` with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: Default::default() }
+	}
+}
+
+/// Error code of failed runtime calls.
+const RUNTIME_ERROR: i64 = 1;
+
+fn runtime_error_into_rpc_err(err: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime error".into(),
+		data: Some(format!("{:?}", err).into()),
+	}
+}
+
+impl<C, Block, AccountId, BlockNumber>
+	MatchmakerApi<<Block as BlockT>::Hash, AccountId, BlockNumber> for Matchmaker<C, Block>
+where
+	Block: BlockT,
+	C: 'static + Send + Sync + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+	C::Api: MatchmakerRuntimeApi<Block, AccountId, BlockNumber>,
+	AccountId: Codec,
+	BlockNumber: Codec,
+{
+	fn bracket_sizes(&self, mode: u8, at: Option<<Block as BlockT>::Hash>) -> Result<Vec<u16>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.bracket_sizes(&at, mode).map_err(runtime_error_into_rpc_err)
+	}
+
+	fn total_queued(&self, mode: u8, at: Option<<Block as BlockT>::Hash>) -> Result<u16> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.total_queued(&at, mode).map_err(runtime_error_into_rpc_err)
+	}
+
+	fn queue_position(
+		&self,
+		account: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<(u8, u8, u16)>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.queue_position(&at, account).map_err(runtime_error_into_rpc_err)
+	}
+
+	fn estimated_wait(
+		&self,
+		account: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<BlockNumber>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.estimated_wait(&at, account).map_err(runtime_error_into_rpc_err)
+	}
+}
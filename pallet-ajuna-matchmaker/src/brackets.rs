@@ -3,7 +3,8 @@
 //! This pallet provides a trait and implementation for a brackets ranked system that
 //! abstracts over storage items and presents them as multiple brackets, with each
 //! having a FIFO queue. This allows an implementation of a matchmaker over different
-//! ranking brackets.
+//! ranking brackets. Brackets are kept apart per game mode, a transient works on the
+//! brackets of a single game mode. Each bracket queue is a `StorageDeque` of item keys.
 //!
 //! Note: You might want to introduce a helper function that wraps the complex
 //! types and just returns the boxed trait object.
@@ -12,6 +13,9 @@ use core::marker::PhantomData;
 use frame_support::storage::{StorageDoubleMap, StorageMap, StorageValue};
 use sp_std::vec::Vec;
 
+pub use crate::deque::BufferIndex;
+use crate::deque::StorageDeque;
+
 /// Trait object presenting the brackets interface.
 pub trait BracketsTrait<ItemKey, Item>
 where
@@ -25,17 +29,44 @@ where
 	/// Implementation note: Call in `drop` to increase ergonomics.
 	fn commit(&self);
 	/// Push an item onto the end of the queue.
-	fn push(&mut self, b: Bracket, j: ItemKey, i: Item) -> bool;
+	///
+	/// Returns an error if the item key is already queued or the queue is full.
+	fn push(&mut self, b: Bracket, j: ItemKey, i: Item) -> Result<(), BracketsError>;
+	/// Push an item onto the start of the queue, ahead of all other items.
+	///
+	/// Returns an error if the item key is already queued or the queue is full.
+	fn push_front(&mut self, b: Bracket, j: ItemKey, i: Item) -> Result<(), BracketsError>;
 	/// Pop an item from the start of the queue.
 	///
 	/// Returns `None` if the queue is empty.
 	fn pop(&mut self, b: Bracket) -> Option<Item>;
+	/// Remove an item from anywhere in the queue, keeping the order of the others.
+	///
+	/// Finding the item is constant, closing the gap is linear in the items queued behind it.
+	///
+	/// Returns `None` if the item key is not queued in that bracket.
+	fn remove(&mut self, b: Bracket, j: ItemKey) -> Option<Item>;
+	/// Return the first item of the queue without removing it.
+	///
+	/// Returns `None` if the queue is empty.
+	fn peek(&self, b: Bracket) -> Option<Item>;
+	/// Return up to `n` items from the start of the queue without removing them.
+	fn front(&self, b: Bracket, n: BufferIndex) -> Vec<Item>;
+	/// Return up to `n` items from the start of the queue that `take` accepts, the items
+	/// it rejects are passed over without removing them.
+	fn front_by<F>(&self, b: Bracket, n: BufferIndex, take: F) -> Vec<Item>
+	where
+		F: FnMut(&Item) -> bool;
 	/// Return whether the queue is empty.
 	fn is_empty(&self, b: Bracket) -> bool;
 	/// Return the size of the brackets queue.
 	fn size(&self, b: Bracket) -> BufferIndex;
-	/// Return whether the item_key is queued or not.
+	/// Return whether the item_key is queued in any game mode or not.
 	fn is_queued(&self, j: ItemKey) -> bool;
+	/// Return the bracket the item_key is queued in.
+	///
+	/// Returns `None` if the item_key is not queued in this game mode.
+	fn bracket_of(&self, j: ItemKey) -> Option<Bracket>;
 }
 
 // There is no equivalent trait in std so we create one.
@@ -62,164 +93,233 @@ impl_wrapping_ops!(u16);
 impl_wrapping_ops!(u32);
 impl_wrapping_ops!(u64);
 
-pub type BufferIndex = u16;
-pub type BufferIndexVector = Vec<(BufferIndex, BufferIndex)>;
 pub type Bracket = u8;
+pub type GameModeId = u8;
+/// Game mode and bracket, identifying a single queue.
+pub type QueueKey = (GameModeId, Bracket);
+/// Game mode, bracket and index of a queued item.
+pub type QueueSlot = (GameModeId, Bracket, BufferIndex);
+
+/// Errors of the brackets queue operations.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BracketsError {
+	/// Item key is already queued in one of the brackets.
+	AlreadyQueued,
+	/// Bracket queue reached its maximum size.
+	QueueFull,
+}
+
+/// Deque of the item keys queued in a single bracket.
+type BracketDeque<ItemKey, B, M> = StorageDeque<QueueKey, ItemKey, B, M>;
 
 /// Transient backing data that is the backbone of the trait object.
-pub struct BracketsTransient<ItemKey, Item, C, B, M, N>
+pub struct BracketsTransient<ItemKey, Item, C, B, M, N, R>
 where
-	ItemKey: Codec + EncodeLike,
+	ItemKey: Codec + EncodeLike + Clone,
 	Item: Codec + EncodeLike,
 	C: StorageValue<Bracket, Query = Bracket>,
-	B: StorageMap<Bracket, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
-	M: StorageDoubleMap<Bracket, BufferIndex, ItemKey, Query = ItemKey>,
-	N: StorageDoubleMap<Bracket, ItemKey, Item, Query = Item>,
+	B: StorageMap<QueueKey, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<QueueKey, BufferIndex, ItemKey, Query = ItemKey>,
+	N: StorageDoubleMap<QueueKey, ItemKey, Item, Query = Item>,
+	R: StorageMap<ItemKey, QueueSlot, Query = Option<QueueSlot>>,
 {
-	index_vector: BufferIndexVector,
-	_phantom: PhantomData<(ItemKey, Item, C, B, M, N)>,
+	mode: GameModeId,
+	brackets: Vec<BracketDeque<ItemKey, B, M>>,
+	_phantom: PhantomData<(Item, C, N, R)>,
 }
 
-impl<ItemKey, Item, C, B, M, N> BracketsTransient<ItemKey, Item, C, B, M, N>
+impl<ItemKey, Item, C, B, M, N, R> BracketsTransient<ItemKey, Item, C, B, M, N, R>
 where
-	ItemKey: Codec + EncodeLike,
+	ItemKey: Codec + EncodeLike + Clone,
 	Item: Codec + EncodeLike,
 	C: StorageValue<Bracket, Query = Bracket>,
-	B: StorageMap<Bracket, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
-	M: StorageDoubleMap<Bracket, BufferIndex, ItemKey, Query = ItemKey>,
-	N: StorageDoubleMap<Bracket, ItemKey, Item, Query = Item>,
+	B: StorageMap<QueueKey, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<QueueKey, BufferIndex, ItemKey, Query = ItemKey>,
+	N: StorageDoubleMap<QueueKey, ItemKey, Item, Query = Item>,
+	R: StorageMap<ItemKey, QueueSlot, Query = Option<QueueSlot>>,
 {
 	/// Create a new `BracketsTransient` that backs the brackets implementation.
 	///
-	/// Initializes itself from the bounds storage `B`.
-	pub fn new() -> BracketsTransient<ItemKey, Item, C, B, M, N> {
+	/// Initializes a deque from the bounds storage `B` for each bracket of a game mode,
+	/// each bracket queue holds at most `max_size` items. The deques commit their bounds
+	/// when they are dropped along with the transient.
+	pub fn new(
+		mode: GameModeId,
+		max_size: BufferIndex,
+	) -> BracketsTransient<ItemKey, Item, C, B, M, N, R> {
 		// get brackets count
 		let brackets_count = C::get();
 
 		// initialize all brackets
-		let mut index_vector = Vec::new();
-		for i in 0..brackets_count {
-			let (start, end) = B::get(i);
-			index_vector.push((start, end));
-		}
+		let brackets = (0..brackets_count)
+			.map(|bracket| BracketDeque::new((mode, bracket), max_size))
+			.collect();
 
-		BracketsTransient { index_vector, _phantom: PhantomData }
-	}
-}
-
-impl<ItemKey, Item, C, B, M, N> Drop for BracketsTransient<ItemKey, Item, C, B, M, N>
-where
-	ItemKey: Codec + EncodeLike,
-	Item: Codec + EncodeLike,
-	C: StorageValue<Bracket, Query = Bracket>,
-	B: StorageMap<Bracket, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
-	M: StorageDoubleMap<Bracket, BufferIndex, ItemKey, Query = ItemKey>,
-	N: StorageDoubleMap<Bracket, ItemKey, Item, Query = Item>,
-{
-	/// Commit on `drop`.
-	fn drop(&mut self) {
-		<Self as BracketsTrait<ItemKey, Item>>::commit(self);
+		BracketsTransient { mode, brackets, _phantom: PhantomData }
 	}
 }
 
 /// Brackets implementation based on `BracketsTransient`
-impl<ItemKey, Item, C, B, M, N> BracketsTrait<ItemKey, Item>
-	for BracketsTransient<ItemKey, Item, C, B, M, N>
+impl<ItemKey, Item, C, B, M, N, R> BracketsTrait<ItemKey, Item>
+	for BracketsTransient<ItemKey, Item, C, B, M, N, R>
 where
-	ItemKey: Codec + EncodeLike,
+	ItemKey: Codec + EncodeLike + Clone,
 	Item: Codec + EncodeLike,
 	C: StorageValue<Bracket, Query = Bracket>,
-	B: StorageMap<Bracket, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
-	M: StorageDoubleMap<Bracket, BufferIndex, ItemKey, Query = ItemKey>,
-	N: StorageDoubleMap<Bracket, ItemKey, Item, Query = Item>,
+	B: StorageMap<QueueKey, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<QueueKey, BufferIndex, ItemKey, Query = ItemKey>,
+	N: StorageDoubleMap<QueueKey, ItemKey, Item, Query = Item>,
+	R: StorageMap<ItemKey, QueueSlot, Query = Option<QueueSlot>>,
 {
 	/// Commit the (potentially) changed bounds to storage.
 	fn commit(&self) {
 		// commit indicies on all brackets
-		for i in 0..self.index_vector.len() {
-			let (v_start, v_end) = self.index_vector[i];
-			B::insert(i as Bracket, (v_start, v_end));
+		for deque in self.brackets.iter() {
+			deque.commit();
 		}
 	}
 
 	/// Push an item onto the end of the queue.
 	///
 	/// Will insert the new item, but will not update the bounds in storage.
-	fn push(&mut self, bracket: Bracket, item_key: ItemKey, item: Item) -> bool {
-		let (mut v_start, mut v_end) = self.index_vector[bracket as usize];
-
-		// check all brackets if key is queued
-		for i in 0..self.index_vector.len() {
-			if N::contains_key(i as Bracket, &item_key) {
-				return false
-			}
+	fn push(
+		&mut self,
+		bracket: Bracket,
+		item_key: ItemKey,
+		item: Item,
+	) -> Result<(), BracketsError> {
+		// check reverse index if key is queued in any bracket of any game mode
+		if R::contains_key(&item_key) {
+			return Err(BracketsError::AlreadyQueued)
 		}
 
-		// insert the item key and the item
-		N::insert(bracket, &item_key, item);
-		M::insert(bracket, v_end, item_key);
-
-		// this will intentionally overflow and wrap around when bonds_end
-		// reaches `Index::max_value` because we want a brackets.
-		let next_index = v_end.wrapping_add(1 as u16);
-		if next_index == v_start {
-			// queue presents as empty but is not
-			// --> overwrite the oldest item in the FIFO brackets
-			v_start = v_start.wrapping_add(1 as u16);
+		// reject instead of overwriting the oldest item in the FIFO brackets
+		let index = self.brackets[bracket as usize]
+			.push_back(item_key.clone())
+			.map_err(|_| BracketsError::QueueFull)?;
+
+		// insert the item and the reverse index of its key
+		N::insert((self.mode, bracket), &item_key, item);
+		R::insert(&item_key, (self.mode, bracket, index));
+		Ok(())
+	}
+
+	/// Push an item onto the start of the queue.
+	///
+	/// Will insert the new item, but will not update the bounds in storage.
+	fn push_front(
+		&mut self,
+		bracket: Bracket,
+		item_key: ItemKey,
+		item: Item,
+	) -> Result<(), BracketsError> {
+		// check reverse index if key is queued in any bracket of any game mode
+		if R::contains_key(&item_key) {
+			return Err(BracketsError::AlreadyQueued)
 		}
-		v_end = next_index;
 
-		self.index_vector[bracket as usize] = (v_start, v_end);
-		true
+		let index = self.brackets[bracket as usize]
+			.push_front(item_key.clone())
+			.map_err(|_| BracketsError::QueueFull)?;
+
+		// insert the item and the reverse index of its key
+		N::insert((self.mode, bracket), &item_key, item);
+		R::insert(&item_key, (self.mode, bracket, index));
+		Ok(())
 	}
 
 	/// Pop an item from the start of the queue.
 	///
 	/// Will remove the item, but will not update the bounds in storage.
 	fn pop(&mut self, bracket: Bracket) -> Option<Item> {
-		if self.is_empty(bracket) {
-			return None
-		}
+		let item_key = self.brackets[bracket as usize].pop_front()?;
+		R::remove(&item_key);
+
+		Some(N::take((self.mode, bracket), item_key))
+	}
+
+	/// Remove an item from anywhere in the queue, keeping the order of the others.
+	///
+	/// Will close the gap by shifting all following items one slot to the front and moving
+	/// their reverse index entries along, so it costs O(n) in the items queued behind it, but
+	/// will not update the bounds in storage.
+	fn remove(&mut self, bracket: Bracket, item_key: ItemKey) -> Option<Item> {
+		// look up the slot of the item key in the reverse index
+		let index = match R::get(&item_key) {
+			Some((m, b, index)) if m == self.mode && b == bracket => index,
+			_ => return None,
+		};
+
+		// keep the reverse index of all following item keys in line with their new slot
+		let mode = self.mode;
+		self.brackets[bracket as usize]
+			.remove(index, |next_key, index| R::insert(next_key, (mode, bracket, index)))?;
+		R::remove(&item_key);
+
+		Some(N::take((self.mode, bracket), item_key))
+	}
 
-		let (mut v_start, v_end) = self.index_vector[bracket as usize];
+	/// Return the first item of the queue without removing it.
+	fn peek(&self, bracket: Bracket) -> Option<Item> {
+		let item_key = self.brackets[bracket as usize].peek_front()?;
 
-		let item_key = M::take(bracket, v_start);
-		let item = N::take(bracket, item_key);
+		Some(N::get((self.mode, bracket), item_key))
+	}
+
+	/// Return up to `n` items from the start of the queue, in queue order.
+	fn front(&self, bracket: Bracket, n: BufferIndex) -> Vec<Item> {
+		let queue_key = (self.mode, bracket);
 
-		v_start = v_start.wrapping_add(1 as u16);
+		self.brackets[bracket as usize]
+			.front(n)
+			.into_iter()
+			.map(|item_key| N::get(queue_key, item_key))
+			.collect()
+	}
 
-		self.index_vector[bracket as usize] = (v_start, v_end);
+	/// Return up to `n` accepted items from the start of the queue, in queue order.
+	fn front_by<F>(&self, bracket: Bracket, n: BufferIndex, mut take: F) -> Vec<Item>
+	where
+		F: FnMut(&Item) -> bool,
+	{
+		let queue_key = (self.mode, bracket);
+		let deque = &self.brackets[bracket as usize];
 
-		item.into()
+		let mut items = Vec::new();
+		let mut index = deque.start();
+		for _i in 0..deque.len() {
+			if items.len() >= n as usize {
+				break
+			}
+			let item = N::get(queue_key, M::get(queue_key, index));
+			if take(&item) {
+				items.push(item);
+			}
+			index = index.wrapping_add(1);
+		}
+		items
 	}
 
 	/// Return whether to consider the queue empty.
 	fn is_empty(&self, bracket: Bracket) -> bool {
-		let (v_start, v_end) = self.index_vector[bracket as usize];
-
-		v_start == v_end
+		self.brackets[bracket as usize].is_empty()
 	}
 
 	/// Return the current size of the ring buffer as a BufferIndex.
 	fn size(&self, bracket: Bracket) -> BufferIndex {
-		let (v_start, v_end) = self.index_vector[bracket as usize];
-
-		if v_start <= v_end {
-			return v_end - v_start
-		} else {
-			return (BufferIndex::MAX - v_start) + v_end
-		}
+		self.brackets[bracket as usize].len()
 	}
 
-	/// Return whether the item_key is queued or not.
+	/// Return whether the item_key is queued in any game mode or not.
 	fn is_queued(&self, item_key: ItemKey) -> bool {
-		// check all brackets if key is queued
-		for i in 0..self.index_vector.len() {
-			if N::contains_key(i as Bracket, &item_key) {
-				return true
-			}
-		}
+		R::contains_key(&item_key)
+	}
 
-		false
+	/// Return the bracket the item_key is queued in, if queued in this game mode.
+	fn bracket_of(&self, item_key: ItemKey) -> Option<Bracket> {
+		match R::get(&item_key) {
+			Some((mode, bracket, _)) if mode == self.mode => Some(bracket),
+			_ => None,
+		}
 	}
 }
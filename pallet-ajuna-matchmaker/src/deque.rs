@@ -0,0 +1,239 @@
+//! # Storage Deque (based on Transient RingBuffer implementation)
+//!
+//! This module provides a bounded double ended queue over two storage items, the bounds of
+//! each queue and the items by index. A single pair of storage items holds any number of
+//! queues, each identified by its own key, like one queue per game mode and bracket.
+//!
+//! The deque is a transient, items are read and written right away while the bounds are only
+//! read on creation and written on `commit`, which is called when the deque is dropped.
+//! Indices wrap around, so a queue can be used forever as long as it stays within its size.
+use codec::{Codec, EncodeLike};
+use core::marker::PhantomData;
+use frame_support::storage::{StorageDoubleMap, StorageMap};
+use sp_std::vec::Vec;
+
+pub type BufferIndex = u16;
+
+/// Errors of the deque operations.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DequeError {
+	/// Deque reached its maximum size.
+	Full,
+}
+
+/// Transient bounded double ended queue, backed by the bounds storage `B` and the item
+/// storage `M`.
+pub struct StorageDeque<Key, Item, B, M>
+where
+	Key: Codec + EncodeLike + Clone,
+	Item: Codec + EncodeLike,
+	B: StorageMap<Key, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<Key, BufferIndex, Item, Query = Item>,
+{
+	key: Key,
+	start: BufferIndex,
+	end: BufferIndex,
+	max_size: BufferIndex,
+	_phantom: PhantomData<(Item, B, M)>,
+}
+
+impl<Key, Item, B, M> StorageDeque<Key, Item, B, M>
+where
+	Key: Codec + EncodeLike + Clone,
+	Item: Codec + EncodeLike,
+	B: StorageMap<Key, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<Key, BufferIndex, Item, Query = Item>,
+{
+	/// Create a new `StorageDeque` for the queue identified by `key`.
+	///
+	/// Initializes itself from the bounds storage `B`, the queue holds at most `max_size`
+	/// items.
+	pub fn new(key: Key, max_size: BufferIndex) -> StorageDeque<Key, Item, B, M> {
+		let (start, end) = B::get(key.clone());
+
+		StorageDeque { key, start, end, max_size, _phantom: PhantomData }
+	}
+
+	/// Commit the (potentially) changed bounds to storage.
+	pub fn commit(&self) {
+		B::insert(self.key.clone(), (self.start, self.end));
+	}
+
+	/// Return the index of the first item, the next one being at the wrapping next index.
+	pub fn start(&self) -> BufferIndex {
+		self.start
+	}
+
+	/// Return the current size of the deque.
+	pub fn len(&self) -> BufferIndex {
+		// indices wrap around, so the distance is taken modulo the index range
+		self.end.wrapping_sub(self.start)
+	}
+
+	/// Return whether the deque is empty.
+	pub fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+
+	/// Return whether the deque reached its maximum size.
+	pub fn is_full(&self) -> bool {
+		self.len() >= self.max_size
+	}
+
+	/// Push an item onto the end of the deque, returns the index it is stored at.
+	pub fn push_back(&mut self, item: Item) -> Result<BufferIndex, DequeError> {
+		// reject instead of overwriting the oldest item
+		if self.is_full() {
+			return Err(DequeError::Full)
+		}
+
+		let index = self.end;
+		M::insert(self.key.clone(), index, item);
+
+		// this will intentionally overflow and wrap around when the end
+		// reaches `BufferIndex::max_value`
+		self.end = self.end.wrapping_add(1);
+		Ok(index)
+	}
+
+	/// Push an item onto the start of the deque, returns the index it is stored at.
+	pub fn push_front(&mut self, item: Item) -> Result<BufferIndex, DequeError> {
+		if self.is_full() {
+			return Err(DequeError::Full)
+		}
+
+		// the start moves one slot back, wrapping around like the end does
+		self.start = self.start.wrapping_sub(1);
+		M::insert(self.key.clone(), self.start, item);
+		Ok(self.start)
+	}
+
+	/// Pop an item from the start of the deque.
+	pub fn pop_front(&mut self) -> Option<Item> {
+		if self.is_empty() {
+			return None
+		}
+
+		let item = M::take(self.key.clone(), self.start);
+		self.start = self.start.wrapping_add(1);
+		Some(item)
+	}
+
+	/// Pop an item from the end of the deque.
+	pub fn pop_back(&mut self) -> Option<Item> {
+		if self.is_empty() {
+			return None
+		}
+
+		self.end = self.end.wrapping_sub(1);
+		Some(M::take(self.key.clone(), self.end))
+	}
+
+	/// Return the first item of the deque without removing it.
+	pub fn peek_front(&self) -> Option<Item> {
+		if self.is_empty() {
+			return None
+		}
+
+		Some(M::get(self.key.clone(), self.start))
+	}
+
+	/// Return the last item of the deque without removing it.
+	pub fn peek_back(&self) -> Option<Item> {
+		if self.is_empty() {
+			return None
+		}
+
+		Some(M::get(self.key.clone(), self.end.wrapping_sub(1)))
+	}
+
+	/// Return up to `n` items from the start of the deque, in queue order.
+	pub fn front(&self, n: BufferIndex) -> Vec<Item> {
+		let mut items = Vec::new();
+		let mut index = self.start;
+		for _i in 0..self.len().min(n) {
+			items.push(M::get(self.key.clone(), index));
+			index = index.wrapping_add(1);
+		}
+		items
+	}
+
+	/// Return whether an index is within the bounds of the deque.
+	pub fn contains_index(&self, index: BufferIndex) -> bool {
+		index.wrapping_sub(self.start) < self.len()
+	}
+
+	/// Remove the item at `index`, keeping the order of the others.
+	///
+	/// Closes the gap by shifting all following items one slot to the front, `on_shift` is
+	/// called with each shifted item and its new index. The cost is linear in the amount of
+	/// following items, each of them is taken and inserted again.
+	pub fn remove<F>(&mut self, index: BufferIndex, mut on_shift: F) -> Option<Item>
+	where
+		F: FnMut(&Item, BufferIndex),
+	{
+		if !self.contains_index(index) {
+			return None
+		}
+
+		let item = M::take(self.key.clone(), index);
+
+		// move all following items one slot to the front
+		let mut index = index;
+		let mut next_index = index.wrapping_add(1);
+		while next_index != self.end {
+			let next_item = M::take(self.key.clone(), next_index);
+			on_shift(&next_item, index);
+			M::insert(self.key.clone(), index, next_item);
+			index = next_index;
+			next_index = next_index.wrapping_add(1);
+		}
+
+		self.end = self.end.wrapping_sub(1);
+		Some(item)
+	}
+}
+
+impl<Key, Item, B, M> StorageDeque<Key, Item, B, M>
+where
+	Key: Codec + EncodeLike + Clone,
+	Item: Codec + EncodeLike + PartialEq,
+	B: StorageMap<Key, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<Key, BufferIndex, Item, Query = Item>,
+{
+	/// Return the index of the first occurrence of an item, searching from the start.
+	pub fn position(&self, item: &Item) -> Option<BufferIndex> {
+		let mut index = self.start;
+		for _i in 0..self.len() {
+			if M::get(self.key.clone(), index) == *item {
+				return Some(index)
+			}
+			index = index.wrapping_add(1);
+		}
+		None
+	}
+
+	/// Return whether an item is in the deque.
+	pub fn contains(&self, item: &Item) -> bool {
+		self.position(item).is_some()
+	}
+
+	/// Remove the first occurrence of an item, keeping the order of the others.
+	pub fn remove_item(&mut self, item: &Item) -> Option<Item> {
+		let index = self.position(item)?;
+		self.remove(index, |_, _| {})
+	}
+}
+
+impl<Key, Item, B, M> Drop for StorageDeque<Key, Item, B, M>
+where
+	Key: Codec + EncodeLike + Clone,
+	Item: Codec + EncodeLike,
+	B: StorageMap<Key, (BufferIndex, BufferIndex), Query = (BufferIndex, BufferIndex)>,
+	M: StorageDoubleMap<Key, BufferIndex, Item, Query = Item>,
+{
+	/// Commit on `drop`.
+	fn drop(&mut self) {
+		self.commit();
+	}
+}
@@ -7,9 +7,19 @@ pub use pallet::*;
 
 use codec::{Decode, Encode, MaxEncodedLen};
 use scale_info::TypeInfo;
-use sp_std::{boxed::Box, vec::Vec};
+use sp_std::{boxed::Box, convert::TryFrom, vec, vec::Vec};
 
-use frame_support::traits::Get;
+use frame_support::{
+	dispatch::{DispatchError, DispatchResult},
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+	weights::Weight,
+	BoundedVec,
+};
+use sp_runtime::{
+	traits::{Saturating, Zero},
+	SaturatedConversion,
+};
 
 #[cfg(test)]
 mod mock;
@@ -20,28 +30,96 @@ mod tests;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod deque;
+
 mod brackets;
 
-use brackets::{Bracket, BracketsTrait, BracketsTransient, BufferIndex};
+use brackets::{Bracket, BracketsError, BracketsTrait, BracketsTransient, QueueKey, QueueSlot};
+
+pub use brackets::GameModeId;
+pub use deque::{BufferIndex, DequeError, StorageDeque};
+
+/// Return whether two units of the bracket fronts, referenced by bracket and position, must
+/// not be matched together.
+type Clashes<'a> = &'a dyn Fn(&(Bracket, usize), &(Bracket, usize)) -> bool;
+
+mod migrations;
 
-#[derive(Encode, Decode, Clone, PartialEq, TypeInfo)]
+mod rating;
+
+pub use rating::{MatchResult, Rating};
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, TypeInfo, MaxEncodedLen)]
 pub enum MatchingType {
-	// ranked matches, if no one in bracket drop down
+	// ranked matches, if no one in bracket drop down within the widened distance
 	Simple,
-	// only allow same bracket matches
+	// only allow same bracket matches, widened by the wait time
 	Same,
-	// take only one of one bracket
+	// take only one of one bracket, within the widened distance
 	Mix,
 }
 
+/// Settings of a game mode, players of each game mode are matched on their own.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, TypeInfo, MaxEncodedLen)]
+pub struct GameModeSettings<BlockNumber> {
+	// how many players a match starts with, once the fill timeout passed
+	pub min_players: u8,
+	// how many players are needed to create a new match right away
+	pub max_players: u8,
+	// blocks a player of the match waits for a full match, zero always waits
+	pub fill_timeout: BlockNumber,
+}
+
+pub type MatchId = u64;
+
+/// Match found, that waits for all of its players to accept it.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, TypeInfo, MaxEncodedLen)]
+pub struct PendingMatch<BlockNumber> {
+	// block until the players have to accept the match
+	deadline: BlockNumber,
+	// amount of matched units, single players or parties
+	units: u8,
+	// amount of players that haven't accepted yet
+	waiting: u8,
+}
+
 #[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug, TypeInfo, MaxEncodedLen)]
-pub struct PlayerStruct<AccountId> {
-	account: AccountId,
+pub struct PlayerStruct<AccountId, BlockNumber, Attributes> {
+	pub account: AccountId,
+	pub queued_at: BlockNumber,
+	// amount of players queued, more than one if the account queued its party
+	pub party_size: u8,
+	// attributes of the account when it queued, like region or client version
+	pub attributes: Attributes,
+}
+
+type BalanceOf<T, I = ()> =
+	<<T as Config<I>>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// Queued player of a matchmaker instance.
+pub type PlayerOf<T, I> = PlayerStruct<
+	<T as frame_system::Config>::AccountId,
+	<T as frame_system::Config>::BlockNumber,
+	<T as Config<I>>::PlayerAttributes,
+>;
+
+/// Decides whether queued players are allowed to be matched with each other, besides their
+/// brackets.
+pub trait MatchFilter<Player> {
+	/// Return whether the candidate can join a match the player was already selected for.
+	fn compatible(player: &Player, candidate: &Player) -> bool;
+}
+
+impl<Player> MatchFilter<Player> for () {
+	fn compatible(_player: &Player, _candidate: &Player) -> bool {
+		true
+	}
 }
 
 #[frame_support::pallet]
 pub mod pallet {
 	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
 
 	// important to use outside structs and consts
 	use super::*;
@@ -52,18 +130,108 @@ pub mod pallet {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
 		type Event: From<Event<Self, I>> + IsType<<Self as frame_system::Config>::Event>;
 
-		/// Constant that indicates how many players are need to create a new match.
+		/// Constant settings of all game modes, a game mode id is the index in this list.
 		#[pallet::constant]
-		type AmountPlayers: Get<u8>;
+		type GameModes: Get<Vec<GameModeSettings<Self::BlockNumber>>>;
 
 		/// Constant that indicates how many ranking brackets exist for players.
 		#[pallet::constant]
 		type AmountBrackets: Get<u8>;
+
+		/// Constant maximum amount of players queued in a single bracket.
+		#[pallet::constant]
+		type MaxQueueSize: Get<BufferIndex>;
+
+		/// Constant maximum amount of players in a party, including its leader.
+		#[pallet::constant]
+		type MaxPartySize: Get<u32>;
+
+		/// Constant amount of blocks a player has to wait, until the allowed distance to
+		/// other brackets grows by one, zero disables widening. Without widening `Same`
+		/// keeps to a single bracket, while `Simple` and `Mix` match across all brackets.
+		#[pallet::constant]
+		type WideningPeriod: Get<Self::BlockNumber>;
+
+		/// Constant amount of blocks a player stays queued before being evicted, zero
+		/// disables expiry.
+		#[pallet::constant]
+		type QueueTimeToLive: Get<Self::BlockNumber>;
+
+		/// Constant maximum amount of expired players evicted in one block.
+		#[pallet::constant]
+		type MaxExpiriesPerBlock: Get<u32>;
+
+		/// Constant amount of blocks matched players have to accept a match, zero disables
+		/// the acceptance and hands out matches right away.
+		#[pallet::constant]
+		type AcceptPeriod: Get<Self::BlockNumber>;
+
+		/// Constant rating loss of players that decline a match or don't accept it in time.
+		#[pallet::constant]
+		type DeclinePenalty: Get<Rating>;
+
+		/// Currency the queue deposit is reserved in.
+		type Currency: ReservableCurrency<Self::AccountId>;
+
+		/// Constant deposit reserved from queued players, returned once they leave the queue
+		/// or their match completed, zero disables it.
+		#[pallet::constant]
+		type QueueDeposit: Get<BalanceOf<Self, I>>;
+
+		/// Constant maximum amount of recent opponents remembered for each player.
+		#[pallet::constant]
+		type MaxRecentOpponents: Get<u32>;
+
+		/// Constant amount of blocks players avoid to be matched again with a recent
+		/// opponent, zero disables the avoidance.
+		#[pallet::constant]
+		type RematchWindow: Get<Self::BlockNumber>;
+
+		/// Attributes players set for themselves and take along into the queue.
+		type PlayerAttributes: Parameter + MaxEncodedLen + Default;
+
+		/// Rules queued players have to satisfy to be matched with each other.
+		type MatchFilter: MatchFilter<
+			PlayerStruct<Self::AccountId, Self::BlockNumber, Self::PlayerAttributes>,
+		>;
+
+		/// Constant maximum amount of matches the matchmaker creates itself in one block, zero
+		/// disables it and leaves matching to `MatchFunc::try_match`.
+		#[pallet::constant]
+		type MaxMatchesPerBlock: Get<u32>;
+
+		/// Handler of the matches the matchmaker creates itself, like the game pallets.
+		type OnMatched: OnMatched<Self::AccountId>;
+
+		/// Origin allowed to switch the matching type, change the amount of brackets and to set
+		/// player attributes.
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+
+		/// Constant matching type used, as long as no other matching type is set.
+		#[pallet::constant]
+		type DefaultMatchingType: Get<MatchingType>;
+
+		/// Constant rating of players that haven't played a rated match yet.
+		#[pallet::constant]
+		type DefaultRating: Get<Rating>;
+
+		/// Constant maximum rating change of a single match, also known as K-factor.
+		#[pallet::constant]
+		type RatingFactor: Get<Rating>;
+
+		/// Constant ascending rating thresholds, each threshold reached moves a player one
+		/// bracket up.
+		#[pallet::constant]
+		type BracketThresholds: Get<Vec<Rating>>;
 	}
 
+	/// The current storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
 	#[pallet::generate_storage_info]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T, I = ()>(_);
 
 	#[pallet::type_value]
@@ -75,6 +243,15 @@ pub mod pallet {
 	pub type BracketsCount<T: Config<I>, I: 'static = ()> =
 		StorageValue<_, u8, ValueQuery, BracketsCountDefault<T, I>>;
 
+	#[pallet::type_value]
+	pub fn MatchingStrategyDefault<T: Config<I>, I: 'static>() -> MatchingType {
+		T::DefaultMatchingType::get()
+	}
+	#[pallet::storage]
+	#[pallet::getter(fn matching_type)]
+	pub type MatchingStrategy<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, MatchingType, ValueQuery, MatchingStrategyDefault<T, I>>;
+
 	// Default value for Nonce
 	#[pallet::type_value]
 	pub fn BracketIndicesDefault<T: Config<I>, I: 'static>() -> (BufferIndex, BufferIndex) {
@@ -85,7 +262,7 @@ pub mod pallet {
 	pub type BracketIndices<T: Config<I>, I: 'static = ()> = StorageMap<
 		_,
 		Blake2_128Concat,
-		Bracket,
+		QueueKey,
 		(BufferIndex, BufferIndex),
 		ValueQuery,
 		BracketIndicesDefault<T, I>,
@@ -96,7 +273,7 @@ pub mod pallet {
 	pub type BracketIndexKeyMap<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
 		_,
 		Blake2_128Concat,
-		Bracket,
+		QueueKey,
 		Blake2_128Concat,
 		BufferIndex,
 		T::AccountId,
@@ -108,22 +285,206 @@ pub mod pallet {
 	pub type BracketKeyValueMap<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
 		_,
 		Blake2_128Concat,
-		Bracket,
+		QueueKey,
+		Blake2_128Concat,
+		T::AccountId,
+		PlayerOf<T, I>,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn key_index)]
+	pub type BracketKeyIndexMap<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, QueueSlot, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn parties)]
+	pub type Parties<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		BoundedVec<T::AccountId, T::MaxPartySize>,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn party_of)]
+	pub type PartyOf<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, T::AccountId, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn party_invites)]
+	pub type PartyInvites<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		Blake2_128Concat,
+		T::AccountId,
+		(),
+		OptionQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn match_count)]
+	pub type MatchCount<T: Config<I>, I: 'static = ()> = StorageValue<_, MatchId, ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn pending_matches)]
+	pub type PendingMatches<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		GameModeId,
+		Blake2_128Concat,
+		MatchId,
+		PendingMatch<T::BlockNumber>,
+		OptionQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn pending_units)]
+	pub type PendingUnits<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		MatchId,
+		Blake2_128Concat,
+		u8,
+		(Bracket, PlayerOf<T, I>),
+		OptionQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn pending_players)]
+	pub type PendingPlayers<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, (GameModeId, MatchId, bool), OptionQuery>;
+
+	/// Units of the match handed to `OnMatched`, by their leader. The match handler can send
+	/// them back to the queue with the block they queued at, until it returns.
+	#[pallet::storage]
+	pub type HandedOut<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
 		Blake2_128Concat,
 		T::AccountId,
-		PlayerStruct<T::AccountId>,
+		(GameModeId, Bracket, PlayerOf<T, I>),
+		OptionQuery,
+	>;
+
+	/// Matches all players accepted, per game mode in the order they were accepted.
+	#[pallet::storage]
+	pub type ReadyIndices<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		GameModeId,
+		(BufferIndex, BufferIndex),
 		ValueQuery,
+		BracketIndicesDefault<T, I>,
 	>;
 
+	#[pallet::storage]
+	pub type ReadyMatches<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		GameModeId,
+		Blake2_128Concat,
+		BufferIndex,
+		MatchId,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn match_deadlines)]
+	pub type MatchDeadlines<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::BlockNumber,
+		Blake2_128Concat,
+		MatchId,
+		GameModeId,
+		OptionQuery,
+	>;
+
+	/// Block of the last match and average blocks between matches of each game mode.
+	#[pallet::storage]
+	#[pallet::getter(fn match_throughput)]
+	pub type MatchThroughput<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, GameModeId, (T::BlockNumber, T::BlockNumber), ValueQuery>;
+
+	/// Queue deposit reserved from each player.
+	#[pallet::storage]
+	#[pallet::getter(fn deposits)]
+	pub type Deposits<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T, I>, OptionQuery>;
+
+	/// Attributes of each player, taken along into the queue.
+	#[pallet::storage]
+	#[pallet::getter(fn attributes)]
+	pub type Attributes<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, T::PlayerAttributes, ValueQuery>;
+
+	/// Recent opponents of each player and the block they were matched with them.
+	#[pallet::storage]
+	#[pallet::getter(fn recent_opponents)]
+	pub type RecentOpponents<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		BoundedVec<(T::AccountId, T::BlockNumber), T::MaxRecentOpponents>,
+		ValueQuery,
+	>;
+
+	#[pallet::type_value]
+	pub fn RatingDefault<T: Config<I>, I: 'static>() -> Rating {
+		T::DefaultRating::get()
+	}
+	#[pallet::storage]
+	#[pallet::getter(fn ratings)]
+	pub type Ratings<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, Rating, ValueQuery, RatingDefault<T, I>>;
+
 	// Pallets use events to inform users when important changes are made.
 	// https://substrate.dev/docs/en/knowledgebase/runtime/events
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event<T: Config<I>, I: 'static = ()> {
-		/// Queued event
-		Queued(PlayerStruct<T::AccountId>),
-		/// Popped event
-		Popped(PlayerStruct<T::AccountId>),
+		/// Queued event [player, bracket, position]
+		Queued(PlayerOf<T, I>, Bracket, BufferIndex),
+		/// Popped event [player, bracket]
+		Popped(PlayerOf<T, I>, Bracket),
+		/// Removed event
+		Removed(PlayerOf<T, I>),
+		/// Expired event
+		Expired(PlayerOf<T, I>),
+		/// Rating changed event
+		RatingChanged(T::AccountId, Rating),
+		/// Party created event [leader]
+		PartyCreated(T::AccountId),
+		/// Party invite event [leader, account]
+		PartyInvited(T::AccountId, T::AccountId),
+		/// Party joined event [leader, account]
+		PartyJoined(T::AccountId, T::AccountId),
+		/// Party left event [leader, account]
+		PartyLeft(T::AccountId, T::AccountId),
+		/// Party disbanded event [leader]
+		PartyDisbanded(T::AccountId),
+		/// Match found event, waiting for its players to accept [match, players]
+		MatchFound(MatchId, Vec<T::AccountId>),
+		/// Match accepted event [match, account]
+		MatchAccepted(MatchId, T::AccountId),
+		/// Match declined event [match, account]
+		MatchDeclined(MatchId, T::AccountId),
+		/// Match not accepted in time event [match]
+		MatchExpired(MatchId),
+		/// Match handed out event, with the bracket each of its players was queued in
+		Matched { players: Vec<T::AccountId>, brackets: Vec<Bracket>, match_id: MatchId },
+		/// Match not taken by any handler event, its players left the queue [match]
+		MatchUnhandled(MatchId),
+		/// Player attributes set event [account]
+		AttributesSet(T::AccountId),
+		/// Queue deposit slashed event [account, amount]
+		DepositSlashed(T::AccountId, BalanceOf<T, I>),
+		/// Amount of brackets changed event [count]
+		BracketsCountSet(u8),
+		/// Matching type changed event [matching type]
+		MatchingTypeSet(MatchingType),
 	}
 
 	// Errors inform users that something went wrong.
@@ -133,13 +494,228 @@ pub mod pallet {
 		QueueSizeToLow,
 		/// Queue is empty.
 		QueueIsEmpty,
+		/// Game mode does not exist.
+		InvalidGameMode,
+		/// Bracket does not exist.
+		InvalidBracket,
+		/// Player is already queued.
+		AlreadyQueued,
+		/// Player is not queued.
+		NotQueued,
+		/// Bracket queue is full.
+		QueueFull,
+		/// Player is already in a party.
+		AlreadyInParty,
+		/// Player is not in a party.
+		NotInParty,
+		/// Only the party leader is allowed to do this.
+		NotPartyLeader,
+		/// Player wasn't invited to this party.
+		NoPartyInvite,
+		/// Party reached its maximum size.
+		PartyFull,
+		/// Party can't be changed while it is queued.
+		PartyQueued,
+		/// Party has more players than a match of the game mode.
+		PartyTooLarge,
+		/// Player has no match to accept or decline.
+		NotMatched,
+		/// Player already accepted the match.
+		AlreadyAccepted,
+		/// Player can't afford the queue deposit.
+		InsufficientDeposit,
+		/// Player still holds the deposit of its last match.
+		DepositHeld,
+		/// Amount of brackets has to be at least one.
+		InvalidBracketsCount,
+		/// Match found waits for its players to accept.
+		AwaitingAcceptance,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config<I>, I: 'static> Hooks<BlockNumberFor<T>> for Pallet<T, I> {
+		// `on_initialize` is executed at the beginning of the block before any extrinsic are
+		// dispatched.
+		//
+		// This function must return the weight consumed by `on_initialize` and `on_finalize`.
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			// requeue players of matches not accepted in time, then evict expired players
+			// before anyone gets matched with them
+			Self::do_expire_matches(now)
+				.saturating_add(Self::do_evict_expired(now))
+				.saturating_add(Self::do_run_matching(now))
+		}
+
+		fn on_runtime_upgrade() -> Weight {
+			migrations::v1::migrate::<T, I>()
+		}
 	}
 
 	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
 	// These functions materialize as "extrinsics", which are often compared to transactions.
 	// Dispatchable functions must be annotated with a weight and must return a DispatchResult.
 	#[pallet::call]
-	impl<T: Config<I>, I: 'static> Pallet<T, I> {}
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		/// Set the attributes of an account, used from the next time it queues.
+		///
+		/// Attributes decide who players are matched with, so they are provided by the admin
+		/// origin instead of the players themselves.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn set_attributes(
+			origin: OriginFor<T>,
+			account: T::AccountId,
+			attributes: T::PlayerAttributes,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<Attributes<T, I>>::insert(&account, attributes);
+
+			Self::deposit_event(Event::AttributesSet(account));
+			Ok(())
+		}
+
+		/// Create a party, led by the sender.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2))]
+		pub fn create_party(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(!<PartyOf<T, I>>::contains_key(&sender), Error::<T, I>::AlreadyInParty);
+			ensure!(!Self::do_is_queued(sender.clone()), Error::<T, I>::AlreadyQueued);
+
+			let mut party = BoundedVec::<T::AccountId, T::MaxPartySize>::default();
+			party.try_push(sender.clone()).map_err(|_| Error::<T, I>::PartyFull)?;
+			<Parties<T, I>>::insert(&sender, party);
+			<PartyOf<T, I>>::insert(&sender, &sender);
+
+			Self::deposit_event(Event::PartyCreated(sender));
+			Ok(())
+		}
+
+		/// Invite an account to the party of the sender, only the leader can invite.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,1))]
+		pub fn invite_to_party(origin: OriginFor<T>, account: T::AccountId) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(Self::party_of(&sender) == Some(sender.clone()), Error::<T, I>::NotPartyLeader);
+			ensure!(!Self::do_is_queued(sender.clone()), Error::<T, I>::PartyQueued);
+			ensure!(!<PartyOf<T, I>>::contains_key(&account), Error::<T, I>::AlreadyInParty);
+
+			<PartyInvites<T, I>>::insert(&sender, &account, ());
+
+			Self::deposit_event(Event::PartyInvited(sender, account));
+			Ok(())
+		}
+
+		/// Join the party of a leader, that invited the sender.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4,3))]
+		pub fn join_party(origin: OriginFor<T>, leader: T::AccountId) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(Self::party_invites(&leader, &sender).is_some(), Error::<T, I>::NoPartyInvite);
+			ensure!(!<PartyOf<T, I>>::contains_key(&sender), Error::<T, I>::AlreadyInParty);
+			ensure!(!Self::do_is_queued(sender.clone()), Error::<T, I>::AlreadyQueued);
+			ensure!(!Self::do_is_queued(leader.clone()), Error::<T, I>::PartyQueued);
+
+			<Parties<T, I>>::try_mutate(&leader, |party| {
+				party.try_push(sender.clone()).map_err(|_| Error::<T, I>::PartyFull)
+			})?;
+			<PartyInvites<T, I>>::remove(&leader, &sender);
+			<PartyOf<T, I>>::insert(&sender, &leader);
+
+			Self::deposit_event(Event::PartyJoined(leader, sender));
+			Ok(())
+		}
+
+		/// Leave the party of the sender, a leaving leader disbands the party.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2))]
+		pub fn leave_party(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let leader = Self::party_of(&sender).ok_or(Error::<T, I>::NotInParty)?;
+			ensure!(!Self::do_is_queued(leader.clone()), Error::<T, I>::PartyQueued);
+
+			if leader == sender {
+				for member in Self::parties(&leader) {
+					<PartyOf<T, I>>::remove(&member);
+				}
+				<Parties<T, I>>::remove(&leader);
+				<PartyInvites<T, I>>::remove_prefix(&leader, None);
+
+				Self::deposit_event(Event::PartyDisbanded(leader));
+			} else {
+				<Parties<T, I>>::mutate(&leader, |party| party.retain(|member| *member != sender));
+				<PartyOf<T, I>>::remove(&sender);
+
+				Self::deposit_event(Event::PartyLeft(leader, sender));
+			}
+			Ok(())
+		}
+
+		/// Accept the match the sender was matched into.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,4))]
+		pub fn accept_match(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let (mode, match_id, accepted) =
+				Self::pending_players(&sender).ok_or(Error::<T, I>::NotMatched)?;
+			ensure!(!accepted, Error::<T, I>::AlreadyAccepted);
+
+			<PendingPlayers<T, I>>::insert(&sender, (mode, match_id, true));
+			let ready = <PendingMatches<T, I>>::mutate(mode, match_id, |pending| {
+				pending.as_mut().map_or(false, |pending| {
+					pending.waiting = pending.waiting.saturating_sub(1);
+					pending.waiting == 0
+				})
+			});
+			if ready {
+				Self::ready_queue(mode)
+					.push_back(match_id)
+					.map_err(|_| Error::<T, I>::QueueFull)?;
+			}
+
+			Self::deposit_event(Event::MatchAccepted(match_id, sender));
+			Ok(())
+		}
+
+		/// Decline the match the sender was matched into, the sender is penalised and all
+		/// other players go back to the front of their bracket.
+		#[pallet::weight(10_000 + Pallet::<T, I>::max_cancel_weight())]
+		pub fn decline_match(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let (mode, match_id, _) =
+				Self::pending_players(&sender).ok_or(Error::<T, I>::NotMatched)?;
+
+			Self::deposit_event(Event::MatchDeclined(match_id, sender.clone()));
+			Self::do_cancel_match(mode, match_id, Some(sender));
+			Ok(())
+		}
+
+		/// Set the amount of brackets, players of removed brackets move into the new top
+		/// bracket or are removed from the queue if it is full.
+		#[pallet::weight(10_000 + Pallet::<T, I>::brackets_count_weight(*count))]
+		pub fn set_brackets_count(origin: OriginFor<T>, count: u8) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			ensure!(count > 0, Error::<T, I>::InvalidBracketsCount);
+
+			Self::do_set_brackets_count(count);
+			Ok(())
+		}
+
+		/// Set the matching type used to fill the matches from now on.
+		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		pub fn set_matching_type(
+			origin: OriginFor<T>,
+			matching_type: MatchingType,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<MatchingStrategy<T, I>>::put(&matching_type);
+			Self::deposit_event(Event::MatchingTypeSet(matching_type));
+			Ok(())
+		}
+	}
 }
 
 impl<T: Config<I>, I: 'static> Pallet<T, I> {
@@ -147,94 +723,1094 @@ impl<T: Config<I>, I: 'static> Pallet<T, I> {
 	///
 	/// Constructs a ringbuffer transient and returns it as a boxed trait object.
 	/// See [this part of the Rust book](https://doc.rust-lang.org/book/ch17-02-trait-objects.html#trait-objects-perform-dynamic-dispatch)
-	fn queue_transient() -> Box<dyn BracketsTrait<T::AccountId, PlayerStruct<T::AccountId>>> {
+	fn queue_transient(mode: GameModeId) -> Box<dyn BracketsTrait<T::AccountId, PlayerOf<T, I>>> {
 		Box::new(BracketsTransient::<
 			T::AccountId,
-			PlayerStruct<T::AccountId>,
+			PlayerOf<T, I>,
 			<Self as Store>::BracketsCount,
 			<Self as Store>::BracketIndices,
 			<Self as Store>::BracketIndexKeyMap,
 			<Self as Store>::BracketKeyValueMap,
-		>::new())
+			<Self as Store>::BracketKeyIndexMap,
+		>::new(mode, T::MaxQueueSize::get()))
+	}
+
+	/// Constructs the deque of the matches of a game mode all players accepted.
+	fn ready_queue(
+		mode: GameModeId,
+	) -> StorageDeque<GameModeId, MatchId, ReadyIndices<T, I>, ReadyMatches<T, I>> {
+		StorageDeque::new(mode, BufferIndex::max_value())
+	}
+
+	fn game_mode(mode: GameModeId) -> Result<GameModeSettings<T::BlockNumber>, DispatchError> {
+		let game_modes = T::GameModes::get();
+		game_modes
+			.get(mode as usize)
+			.cloned()
+			.ok_or(Error::<T, I>::InvalidGameMode.into())
+	}
+
+	fn ensure_queue(
+		mode: GameModeId,
+		bracket: u8,
+	) -> Result<GameModeSettings<T::BlockNumber>, DispatchError> {
+		let game_mode = Self::game_mode(mode)?;
+		ensure!(bracket < Self::brackets_count(), Error::<T, I>::InvalidBracket);
+		Ok(game_mode)
+	}
+
+	/// Return the leader of the party an account is in, or the account itself.
+	fn party_leader(account: T::AccountId) -> T::AccountId {
+		Self::party_of(&account).unwrap_or(account)
+	}
+
+	/// Return the players queued by an account, its whole party if it is a party leader.
+	fn party_members(account: &T::AccountId) -> Vec<T::AccountId> {
+		let party = Self::parties(account);
+		if party.is_empty() {
+			return vec![account.clone()]
+		}
+		party.into_inner()
 	}
 
-	fn do_add_queue(account: T::AccountId, bracket: u8) -> bool {
-		let mut queue = Self::queue_transient();
+	fn do_add_queue(account: T::AccountId, mode: GameModeId, bracket: u8) -> DispatchResult {
+		let game_mode = Self::ensure_queue(mode, bracket)?;
 
-		let player = PlayerStruct { account };
-		// duplicate check if we can add key to the queue
-		if !queue.push(bracket, player.account.clone(), player.clone()) {
-			return false
+		// party members are queued by their leader
+		ensure!(Self::party_leader(account.clone()) == account, Error::<T, I>::NotPartyLeader);
+		ensure!(!<PendingPlayers<T, I>>::contains_key(&account), Error::<T, I>::AlreadyQueued);
+		let party_size = Self::party_members(&account).len().saturated_into::<u8>();
+		ensure!(party_size <= game_mode.max_players, Error::<T, I>::PartyTooLarge);
+
+		let mut queue = Self::queue_transient(mode);
+
+		let player = PlayerStruct {
+			attributes: Self::attributes(&account),
+			account,
+			queued_at: <frame_system::Pallet<T>>::block_number(),
+			party_size,
+		};
+		// duplicate and queue size check if we can add key to the queue
+		match queue.push(bracket, player.account.clone(), player.clone()) {
+			Err(BracketsError::AlreadyQueued) => return Err(Error::<T, I>::AlreadyQueued)?,
+			Err(BracketsError::QueueFull) => return Err(Error::<T, I>::QueueFull)?,
+			Ok(()) => {},
+		}
+		// every player of the unit has to afford the deposit
+		if let Err(e) = Self::reserve_deposits(&Self::party_members(&player.account)) {
+			queue.remove(bracket, player.account);
+			return Err(e)
 		}
 
-		Self::deposit_event(Event::Queued(player));
-		true
+		let position = queue.size(bracket).saturating_sub(1);
+		Self::deposit_event(Event::Queued(player, bracket, position));
+		Ok(())
 	}
 
-	fn do_empty_queue(bracket: u8) {
-		let mut queue = Self::queue_transient();
+	fn do_add_queue_by_rating(account: T::AccountId, mode: GameModeId) -> DispatchResult {
+		// a party is queued with the average rating of its players
+		let members = Self::party_members(&account);
+		let total: u32 = members.iter().map(|member| Self::ratings(member) as u32).sum();
+		let rating = (total / members.len() as u32).saturated_into::<Rating>();
 
-		while queue.size(bracket) > 0 {
-			queue.pop(bracket);
+		let bracket = Self::do_rating_bracket(rating);
+		Self::do_add_queue(account, mode, bracket)
+	}
+
+	fn do_rating_bracket(rating: Rating) -> Bracket {
+		rating::bracket(rating, &T::BracketThresholds::get(), Self::brackets_count())
+	}
+
+	fn do_report_result(account: T::AccountId, opponent: T::AccountId, result: MatchResult) {
+		let (rating, opponent_rating) = rating::rate(
+			Self::ratings(&account),
+			Self::ratings(&opponent),
+			&result,
+			T::RatingFactor::get(),
+		);
+
+		<Ratings<T, I>>::insert(&account, rating);
+		<Ratings<T, I>>::insert(&opponent, opponent_rating);
+
+		Self::deposit_event(Event::RatingChanged(account, rating));
+		Self::deposit_event(Event::RatingChanged(opponent, opponent_rating));
+	}
+
+	fn do_leave_queue(account: T::AccountId) -> DispatchResult {
+		// remove account, or its whole party, from the game mode and bracket it is queued in
+		let leader = Self::party_leader(account);
+		if let Some((mode, bracket, _)) = Self::key_index(&leader) {
+			if let Some(p) = Self::queue_transient(mode).remove(bracket, leader) {
+				Self::release_deposits(&p.account);
+				Self::deposit_event(Event::Removed(p));
+				return Ok(())
+			}
 		}
+
+		Err(Error::<T, I>::NotQueued)?
 	}
 
-	fn do_all_empty_queue() {
+	fn do_evict_expired(now: T::BlockNumber) -> Weight {
+		// initial weights
+		let mut tot_weights = 10_000;
+
+		let time_to_live = T::QueueTimeToLive::get();
+		if time_to_live.is_zero() {
+			return tot_weights
+		}
+
+		let max_expiries = T::MaxExpiriesPerBlock::get();
+
+		let mut expiries: u32 = 0;
+		for mode in 0..T::GameModes::get().len() as GameModeId {
+			let mut queue = Self::queue_transient(mode);
+
+			for i in 0..Self::brackets_count() {
+				// players are queued in order, so expired players are always in front
+				while expiries < max_expiries {
+					match queue.peek(i) {
+						Some(p) if now.saturating_sub(p.queued_at) >= time_to_live => {
+							queue.pop(i);
+							Self::release_deposits(&p.account);
+							Self::deposit_event(Event::Expired(p));
+							expiries += 1;
+							// weights need to be adjusted
+							tot_weights = tot_weights + T::DbWeight::get().reads_writes(2, 2);
+						},
+						_ => break,
+					}
+				}
+				// bracket indices and peeked player
+				tot_weights = tot_weights + T::DbWeight::get().reads_writes(3, 1);
+			}
+		}
+
+		tot_weights
+	}
+
+	fn do_empty_queue(mode: GameModeId, bracket: u8) -> DispatchResult {
+		Self::ensure_queue(mode, bracket)?;
+
+		let mut queue = Self::queue_transient(mode);
+
+		while let Some(p) = queue.pop(bracket) {
+			Self::release_deposits(&p.account);
+		}
+		Ok(())
+	}
+
+	fn do_all_empty_queue(mode: GameModeId) -> DispatchResult {
+		Self::game_mode(mode)?;
+
+		let mut queue = Self::queue_transient(mode);
+
 		for i in 0..Self::brackets_count() {
-			Self::do_empty_queue(i);
+			while let Some(p) = queue.pop(i) {
+				Self::release_deposits(&p.account);
+			}
+		}
+		Self::drop_pending_matches(mode);
+		Ok(())
+	}
+
+	/// Weight of removing a unit from its bracket, with every other slot of the bracket queued
+	/// behind it and shifted to the front.
+	fn do_leave_queue_weight() -> Weight {
+		let db = T::DbWeight::get();
+		let shifted = Weight::from(T::MaxQueueSize::get());
+		let members = Weight::from(T::MaxPartySize::get());
+		// weights need to be adjusted
+		db.reads_writes(5, 4)
+			.saturating_add(db.reads_writes(1, 3).saturating_mul(shifted))
+			.saturating_add(db.reads_writes(1, 1).saturating_mul(members))
+	}
+
+	/// Drop the pending matches of a game mode, their players leave without a penalty.
+	fn drop_pending_matches(mode: GameModeId) {
+		let mut ready = Self::ready_queue(mode);
+		while ready.pop_front().is_some() {}
+
+		let pending: Vec<(MatchId, PendingMatch<T::BlockNumber>)> =
+			<PendingMatches<T, I>>::drain_prefix(mode).collect();
+		for (match_id, pending) in pending {
+			<MatchDeadlines<T, I>>::remove(pending.deadline, match_id);
+			for unit in 0..pending.units {
+				if let Some((_, p)) = <PendingUnits<T, I>>::take(match_id, unit) {
+					for member in Self::party_members(&p.account) {
+						<PendingPlayers<T, I>>::remove(&member);
+					}
+					Self::release_deposits(&p.account);
+					Self::deposit_event(Event::Removed(p));
+				}
+			}
+		}
+	}
+
+	/// Weight of emptying the queues of a game mode, with every bracket full and the matches
+	/// of a whole accept period pending.
+	fn do_all_empty_queue_weight() -> Weight {
+		let db = T::DbWeight::get();
+		let queued =
+			Weight::from(Self::brackets_count()).saturating_mul(T::MaxQueueSize::get().into());
+		let pending = Weight::from(T::MaxMatchesPerBlock::get())
+			.saturating_mul(T::AcceptPeriod::get().saturated_into::<Weight>().saturating_add(1));
+		// weights need to be adjusted
+		db.reads_writes(3, 2)
+			.saturating_add(db.reads_writes(3, 4).saturating_mul(queued))
+			.saturating_add(Self::max_cancel_weight().saturating_mul(pending))
+	}
+
+	/// Weight of one attempt to take a match, at worst every queued unit of each bracket is
+	/// read along with the attributes and recent opponents of its members, and each unit of
+	/// the largest match is removed from the middle of its bracket.
+	fn take_match_weight() -> Weight {
+		let db = T::DbWeight::get();
+		let queued =
+			Weight::from(Self::brackets_count()).saturating_mul(T::MaxQueueSize::get().into());
+		let members = queued.saturating_mul(T::MaxPartySize::get().into());
+		let units = T::GameModes::get()
+			.iter()
+			.map(|mode| mode.max_players as Weight)
+			.max()
+			.unwrap_or(0);
+		// weights need to be adjusted
+		db.reads_writes(5, 4)
+			.saturating_add(db.reads(2).saturating_mul(queued))
+			.saturating_add(db.reads(3).saturating_mul(members))
+			.saturating_add(Self::do_leave_queue_weight().saturating_mul(units))
+	}
+
+	/// Create matches up to the budget of a block and hand them to the match handler, the
+	/// game modes take turns to go first.
+	fn do_run_matching(now: T::BlockNumber) -> Weight {
+		// initial weights
+		let mut tot_weights = 10_000;
+
+		let max_matches = T::MaxMatchesPerBlock::get();
+		let modes = T::GameModes::get().len();
+		if max_matches == 0 || modes == 0 {
+			return tot_weights
+		}
+
+		let first = (now % T::BlockNumber::from(modes as u32)).saturated_into::<usize>();
+		let attempt_weight = Self::take_match_weight();
+		let mut matches = 0;
+		for i in 0..modes {
+			let mode = ((first + i) % modes) as GameModeId;
+			while matches < max_matches {
+				tot_weights = tot_weights.saturating_add(attempt_weight);
+				// players are held back while the handler can't start their match
+				if !T::OnMatched::has_capacity(mode) {
+					break
+				}
+				match Self::do_take_match(mode) {
+					Ok(Some((match_id, players))) => {
+						let (taken, weight) =
+							T::OnMatched::on_matched(mode, match_id, players.clone());
+						Self::clear_handed_out(&players);
+						tot_weights = tot_weights +
+							weight + T::DbWeight::get().writes(players.len() as Weight);
+						// players of a match no one took don't wait for a game forever
+						if !taken {
+							for player in &players {
+								Self::do_release_deposit(player);
+							}
+							Self::deposit_event(Event::MatchUnhandled(match_id));
+							tot_weights =
+								tot_weights + T::DbWeight::get().writes(players.len() as Weight);
+						}
+						matches += 1;
+					},
+					// a match waiting for its players to accept uses up the budget as well
+					Ok(None) => matches += 1,
+					Err(_) => break,
+				}
+			}
+		}
+
+		tot_weights
+	}
+
+	fn do_try_match(mode: GameModeId) -> Result<Vec<T::AccountId>, DispatchError> {
+		let (_, players) = Self::do_take_match(mode)?.ok_or(Error::<T, I>::AwaitingAcceptance)?;
+		Self::clear_handed_out(&players);
+		Ok(players)
+	}
+
+	/// Forget the units of a match handed out, they can't be requeued anymore.
+	fn clear_handed_out(players: &[T::AccountId]) {
+		for player in players {
+			<HandedOut<T, I>>::remove(player);
+		}
+	}
+
+	/// Send a unit of the match handed out back to the front of its bracket, with the block
+	/// it originally queued at, so the wait of its players is kept.
+	fn do_requeue(account: T::AccountId) -> DispatchResult {
+		let (mode, bracket, player) =
+			<HandedOut<T, I>>::take(&account).ok_or(Error::<T, I>::NotMatched)?;
+
+		let mut queue = Self::queue_transient(mode);
+		match queue.push_front(bracket, account.clone(), player.clone()) {
+			Err(BracketsError::AlreadyQueued) => return Err(Error::<T, I>::AlreadyQueued)?,
+			Err(BracketsError::QueueFull) => return Err(Error::<T, I>::QueueFull)?,
+			Ok(()) => {},
+		}
+		// every player of the unit has to afford the deposit again
+		if let Err(e) = Self::reserve_deposits(&Self::party_members(&account)) {
+			queue.remove(bracket, account);
+			return Err(e)
+		}
+
+		Self::deposit_event(Event::Queued(player, bracket, 0));
+		Ok(())
+	}
+
+	/// Return the id and the players of the next match handed out, or none if the match
+	/// found has to be accepted by its players first.
+	///
+	/// At most one match is found per call, matches all players accepted go first.
+	fn do_take_match(
+		mode: GameModeId,
+	) -> Result<Option<(MatchId, Vec<T::AccountId>)>, DispatchError> {
+		if let Some((match_id, units)) = Self::take_accepted_match(mode) {
+			return Ok(Some((match_id, Self::hand_out_match(match_id, mode, units))))
+		}
+
+		let units = Self::do_pop_match(mode)?;
+		let accept_period = T::AcceptPeriod::get();
+		if accept_period.is_zero() {
+			let match_id = Self::next_match_id();
+			let units = units
+				.into_iter()
+				.map(|(bracket, p)| {
+					let members = Self::party_members(&p.account);
+					<HandedOut<T, I>>::insert(&p.account, (mode, bracket, p));
+					(bracket, members)
+				})
+				.collect();
+			return Ok(Some((match_id, Self::hand_out_match(match_id, mode, units))))
 		}
+		// players have to accept the match, before it is handed out
+		Self::create_pending_match(mode, units, accept_period);
+		Ok(None)
+	}
+
+	/// Return a new match id, match ids count up for each match found.
+	fn next_match_id() -> MatchId {
+		let match_id = Self::match_count();
+		<MatchCount<T, I>>::put(match_id.wrapping_add(1));
+		match_id
+	}
+
+	/// Record a match handed out and return its players.
+	fn hand_out_match(
+		match_id: MatchId,
+		mode: GameModeId,
+		units: Vec<(Bracket, Vec<T::AccountId>)>,
+	) -> Vec<T::AccountId> {
+		let (brackets, units): (Vec<Bracket>, Vec<Vec<T::AccountId>>) = units.into_iter().unzip();
+		Self::record_match(mode);
+		Self::record_opponents(&units);
+
+		// every player of a unit was queued in the bracket of the unit
+		let brackets = brackets
+			.iter()
+			.zip(units.iter())
+			.flat_map(|(bracket, unit)| vec![*bracket; unit.len()])
+			.collect();
+		let players = units.concat();
+		Self::deposit_event(Event::Matched { players: players.clone(), brackets, match_id });
+		players
+	}
+
+	/// Remember the players of all other units of a match as recent opponents of each player.
+	fn record_opponents(units: &[Vec<T::AccountId>]) {
+		let window = T::RematchWindow::get();
+		if window.is_zero() {
+			return
+		}
+
+		let now = <frame_system::Pallet<T>>::block_number();
+		let max_opponents = T::MaxRecentOpponents::get() as usize;
+		for (i, unit) in units.iter().enumerate() {
+			let opponents: Vec<T::AccountId> = units
+				.iter()
+				.enumerate()
+				.filter(|(j, _)| *j != i)
+				.flat_map(|(_, other)| other.iter().cloned())
+				.collect();
+			for account in unit {
+				// forget opponents out of the window and the ones met again
+				let mut recent: Vec<(T::AccountId, T::BlockNumber)> =
+					Self::recent_opponents(account)
+						.into_inner()
+						.into_iter()
+						.filter(|(o, at)| {
+							now.saturating_sub(*at) < window && !opponents.contains(o)
+						})
+						.collect();
+				recent.extend(opponents.iter().map(|o| (o.clone(), now)));
+				// only the latest opponents are kept
+				let excess = recent.len().saturating_sub(max_opponents);
+				recent.drain(..excess);
+				<RecentOpponents<T, I>>::insert(
+					account,
+					BoundedVec::try_from(recent).unwrap_or_default(),
+				);
+			}
+		}
+	}
+
+	/// Track the average blocks between handed out matches of a game mode.
+	fn record_match(mode: GameModeId) {
+		let now = <frame_system::Pallet<T>>::block_number();
+		let (last_match, interval) = Self::match_throughput(mode);
+
+		let elapsed = now.saturating_sub(last_match);
+		// moving average, the latest interval weights a quarter
+		let interval = if last_match.is_zero() {
+			elapsed
+		} else {
+			interval.saturating_mul(3u32.into()).saturating_add(elapsed) / 4u32.into()
+		};
+		<MatchThroughput<T, I>>::insert(mode, (now, interval));
 	}
 
-	fn do_try_match() -> Vec<T::AccountId> {
-		let mut queue = Self::queue_transient();
-		let max_players = T::AmountPlayers::get();
+	/// Remove the units of the next match from the brackets of a game mode.
+	fn do_pop_match(mode: GameModeId) -> Result<Vec<(Bracket, PlayerOf<T, I>)>, DispatchError> {
+		let game_mode = Self::game_mode(mode)?;
+		let max_players = game_mode.max_players;
+		let mut queue = Self::queue_transient(mode);
 
-		let mut result: Vec<T::AccountId> = Vec::new();
-		let mut brackets: Vec<Bracket> = Vec::new();
-		// pass trough all brackets
+		// A match holds at most `max_players / size` units of a party size, the quota of each
+		// size is twice that, to look past units that clash with the ones already selected.
+		// Further units of a size that used up its quota are passed over, so parties that
+		// don't fit together can't hide the units queued behind them. The window holds the
+		// quotas of the party sizes up to `MaxPartySize`, at worst the whole bracket is read.
+		let max_party_size = T::MaxPartySize::get().saturated_into::<u8>().min(max_players);
+		let quotas: Vec<u8> = (0..=max_party_size)
+			.map(|size| if size == 0 { 0 } else { (max_players / size).saturating_mul(2) })
+			.collect();
+		let window: BufferIndex = quotas.iter().map(|quota| *quota as BufferIndex).sum();
+
+		let mut result: Vec<(Bracket, PlayerOf<T, I>)> = Vec::new();
+		let mut fronts: Vec<Vec<PlayerOf<T, I>>> = Vec::new();
+		let mut distances: Vec<Bracket> = Vec::new();
 		for i in 0..Self::brackets_count() {
+			let mut left = quotas.clone();
+			let front = queue.front_by(i, window, |p| match left.get_mut(p.party_size as usize) {
+				Some(quota) if *quota > 0 => {
+					*quota -= 1;
+					true
+				},
+				_ => false,
+			});
+			// distance is widened by the wait of the longest queued player
+			distances.push(front.first().map_or(0, |p| Self::widened_distance(p.queued_at)));
+			fronts.push(front);
+		}
+
+		// nothing to match if no one is queued
+		ensure!(fronts.iter().any(|front| !front.is_empty()), Error::<T, I>::QueueIsEmpty);
+
+		// a match starts with less players, once one of its own units waited the fill timeout
+		let now = <frame_system::Pallet<T>>::block_number();
+		let timed_out: Vec<Vec<bool>> = fronts
+			.iter()
+			.map(|front| {
+				front
+					.iter()
+					.map(|p| {
+						!game_mode.fill_timeout.is_zero() &&
+							now.saturating_sub(p.queued_at) >= game_mode.fill_timeout
+					})
+					.collect()
+			})
+			.collect();
+		let min_players = if timed_out.iter().flatten().any(|timed_out| *timed_out) {
+			game_mode.min_players
+		} else {
+			max_players
+		};
+
+		// a single player or a whole party is one unit, that takes a slot for each player
+		let units: Vec<Vec<u8>> = fronts
+			.iter()
+			.map(|front| front.iter().map(|p| p.party_size).collect())
+			.collect();
+
+		let window = T::RematchWindow::get();
+		let members: Vec<Vec<Vec<T::AccountId>>> = fronts
+			.iter()
+			.map(|front| front.iter().map(|p| Self::party_members(&p.account)).collect())
+			.collect();
+		// opponents the players of each unit met within the rematch window
+		let recent: Vec<Vec<Vec<T::AccountId>>> = members
+			.iter()
+			.map(|bracket| {
+				bracket
+					.iter()
+					.map(|unit| {
+						unit.iter()
+							.flat_map(|account| Self::recent_opponents(account).into_inner())
+							.filter(|(_, at)| now.saturating_sub(*at) < window)
+							.map(|(o, _)| o)
+							.collect()
+					})
+					.collect()
+			})
+			.collect();
+		let avoid_recent = recent.iter().flatten().any(|opponents| !opponents.is_empty());
+		// units only meet units of brackets within the distance one of them waited for
+		let widening = !T::WideningPeriod::get().is_zero();
+		let out_of_reach = |a: &(Bracket, usize), b: &(Bracket, usize)| {
+			let reach = Self::widened_distance(fronts[a.0 as usize][a.1].queued_at)
+				.max(Self::widened_distance(fronts[b.0 as usize][b.1].queued_at));
+			widening && a.0.max(b.0) - a.0.min(b.0) > reach
+		};
+		// every player of a unit, party members with their current attributes
+		let players: Vec<Vec<Vec<PlayerOf<T, I>>>> = fronts
+			.iter()
+			.zip(members.iter())
+			.map(|(front, bracket)| {
+				front
+					.iter()
+					.zip(bracket.iter())
+					.map(|(p, unit)| {
+						unit.iter()
+							.map(|member| {
+								if *member == p.account {
+									return p.clone()
+								}
+								PlayerStruct {
+									account: member.clone(),
+									attributes: Self::attributes(member),
+									..p.clone()
+								}
+							})
+							.collect()
+					})
+					.collect()
+			})
+			.collect();
+		// players have to satisfy the rules of the match filter
+		let incompatible = |a: &(Bracket, usize), b: &(Bracket, usize)| {
+			let candidates = &players[b.0 as usize][b.1];
+			players[a.0 as usize][a.1].iter().any(|player| {
+				candidates
+					.iter()
+					.any(|candidate| !T::MatchFilter::compatible(player, candidate))
+			})
+		};
+		let avoided = |a: &(Bracket, usize), b: &(Bracket, usize)| {
+			let opponents = &recent[a.0 as usize][a.1];
+			incompatible(a, b) ||
+				members[b.0 as usize][b.1].iter().any(|account| opponents.contains(account))
+		};
+
+		let select = |clashes: Clashes| {
+			let widened =
+				|a: &(Bracket, usize), b: &(Bracket, usize)| out_of_reach(a, b) || clashes(a, b);
+			match Self::matching_type() {
+				MatchingType::Simple =>
+					Self::simple_brackets(&units, max_players, min_players, &timed_out, &widened),
+				MatchingType::Same => Self::same_brackets(
+					&units,
+					&distances,
+					max_players,
+					min_players,
+					&timed_out,
+					clashes,
+				),
+				MatchingType::Mix =>
+					Self::mix_brackets(&units, max_players, min_players, &timed_out, &widened),
+			}
+		};
+		// skip recent opponents, unless they are the only players to match
+		let mut selected = select(&avoided);
+		if selected.is_empty() && avoid_recent {
+			selected = select(&incompatible);
+		}
+		// no units found filling all slots leave
+		if selected.is_empty() {
+			return Err(Error::<T, I>::QueueSizeToLow)?
+		}
+
+		// remove the harvested units from their brackets
+		for (bracket, unit) in selected {
+			let account = fronts[bracket as usize][unit].account.clone();
+			if let Some(p) = queue.remove(bracket, account) {
+				result.push((bracket, p.clone()));
+				Self::deposit_event(Event::Popped(p, bracket));
+			}
+		}
+		// return result
+		Ok(result)
+	}
+
+	/// Store the units of a match, that waits for all of its players to accept it.
+	fn create_pending_match(
+		mode: GameModeId,
+		units: Vec<(Bracket, PlayerOf<T, I>)>,
+		accept_period: T::BlockNumber,
+	) {
+		let match_id = Self::next_match_id();
+
+		let deadline = <frame_system::Pallet<T>>::block_number().saturating_add(accept_period);
+		let units_count = units.len().saturated_into::<u8>();
+
+		let mut players: Vec<T::AccountId> = Vec::new();
+		for (i, (bracket, p)) in units.into_iter().enumerate() {
+			for member in Self::party_members(&p.account) {
+				<PendingPlayers<T, I>>::insert(&member, (mode, match_id, false));
+				players.push(member);
+			}
+			<PendingUnits<T, I>>::insert(match_id, i as u8, (bracket, p));
+		}
+
+		let pending = PendingMatch {
+			deadline,
+			units: units_count,
+			waiting: players.len().saturated_into::<u8>(),
+		};
+		<PendingMatches<T, I>>::insert(mode, match_id, pending);
+		<MatchDeadlines<T, I>>::insert(deadline, match_id, mode);
+
+		Self::deposit_event(Event::MatchFound(match_id, players));
+	}
+
+	/// Remove a match all players accepted and return its id and the bracket and players of
+	/// each of its units.
+	fn take_accepted_match(
+		mode: GameModeId,
+	) -> Option<(MatchId, Vec<(Bracket, Vec<T::AccountId>)>)> {
+		// matches declined after all players accepted are skipped, they are gone already
+		let mut ready = Self::ready_queue(mode);
+		let (match_id, pending) = loop {
+			let match_id = ready.pop_front()?;
+			if let Some(pending) = <PendingMatches<T, I>>::take(mode, match_id) {
+				break (match_id, pending)
+			}
+		};
+		<MatchDeadlines<T, I>>::remove(pending.deadline, match_id);
+
+		let mut units: Vec<(Bracket, Vec<T::AccountId>)> = Vec::new();
+		for unit in 0..pending.units {
+			if let Some((bracket, p)) = <PendingUnits<T, I>>::take(match_id, unit) {
+				let members = Self::party_members(&p.account);
+				for member in &members {
+					<PendingPlayers<T, I>>::remove(member);
+				}
+				<HandedOut<T, I>>::insert(&p.account, (mode, bracket, p));
+				units.push((bracket, members));
+			}
+		}
+		Some((match_id, units))
+	}
+
+	/// Cancel a pending match, that was declined or not accepted in time.
+	///
+	/// The decliner, or on timeout every player that didn't accept, is penalised and its
+	/// unit removed. All other units go back to the front of their bracket.
+	///
+	/// Returns the weight consumed, growing with the units and players of the match.
+	fn do_cancel_match(
+		mode: GameModeId,
+		match_id: MatchId,
+		decliner: Option<T::AccountId>,
+	) -> Weight {
+		let pending = match <PendingMatches<T, I>>::take(mode, match_id) {
+			Some(pending) => pending,
+			None => return Self::cancel_weight(0, 0),
+		};
+		<MatchDeadlines<T, I>>::remove(pending.deadline, match_id);
+
+		let mut members_count: u32 = 0;
+
+		// the bracket may have been removed while the match was pending
+		let top_bracket = Self::brackets_count().saturating_sub(1);
+		let mut queue = Self::queue_transient(mode);
+		// units are pushed to the front in reverse, so they keep their order in the queue
+		for unit in (0..pending.units).rev() {
+			if let Some((bracket, p)) = <PendingUnits<T, I>>::take(match_id, unit) {
+				let bracket = bracket.min(top_bracket);
+				let mut requeue = true;
+				for member in Self::party_members(&p.account) {
+					members_count += 1;
+					let accepted =
+						matches!(<PendingPlayers<T, I>>::take(&member), Some((_, _, true)));
+					let penalised = match &decliner {
+						Some(decliner) => *decliner == member,
+						None => !accepted,
+					};
+					if penalised {
+						requeue = false;
+						Self::do_slash_deposit(&member);
+						Self::do_penalise(member);
+					}
+				}
+
+				if requeue && queue.push_front(bracket, p.account.clone(), p.clone()).is_ok() {
+					Self::deposit_event(Event::Queued(p, bracket, 0));
+				} else {
+					Self::release_deposits(&p.account);
+					Self::deposit_event(Event::Removed(p));
+				}
+			}
+		}
+
+		Self::cancel_weight(pending.units.into(), members_count)
+	}
+
+	/// Weight of cancelling a match of `units` units with `members` players in total.
+	fn cancel_weight(units: u32, members: u32) -> Weight {
+		let db = T::DbWeight::get();
+		// weights need to be adjusted
+		db.reads_writes(3, 2)
+			.saturating_add(db.reads_writes(3, 3).saturating_mul(units.into()))
+			.saturating_add(db.reads_writes(3, 4).saturating_mul(members.into()))
+	}
+
+	/// Weight of cancelling a match of the largest game mode, each player in its own unit.
+	fn max_cancel_weight() -> Weight {
+		let players = T::GameModes::get()
+			.iter()
+			.map(|mode| mode.max_players as u32)
+			.max()
+			.unwrap_or(0);
+		Self::cancel_weight(players, players)
+	}
+
+	/// Change the amount of brackets, returns the amount of players moved or removed.
+	///
+	/// Players of removed brackets are merged into the new top bracket by the block they
+	/// queued at, so expiry still finds the longest queued players in front. The latest
+	/// queued players that don't fit anymore are removed from the queue.
+	pub(crate) fn do_set_brackets_count(count: u8) -> u32 {
+		let old_count = Self::brackets_count();
+		let top = count.saturating_sub(1);
+
+		let mut moved: u32 = 0;
+		if count < old_count {
+			for mode in 0..T::GameModes::get().len() as GameModeId {
+				{
+					let mut queue = Self::queue_transient(mode);
+					let mut players: Vec<(Bracket, PlayerOf<T, I>)> = Vec::new();
+					for bracket in top..old_count {
+						while let Some(p) = queue.pop(bracket) {
+							players.push((bracket, p));
+						}
+					}
+					// the sort is stable, players queued at the same block keep their order
+					players.sort_by_key(|(_, p)| p.queued_at);
+
+					for (bracket, p) in players {
+						let pushed = queue.push(top, p.account.clone(), p.clone()).is_ok();
+						if bracket != top || !pushed {
+							moved += 1;
+						}
+						if !pushed {
+							Self::release_deposits(&p.account);
+							Self::deposit_event(Event::Removed(p));
+						} else if bracket != top {
+							let position = queue.size(top).saturating_sub(1);
+							Self::deposit_event(Event::Queued(p, top, position));
+						}
+					}
+				}
+				// bounds are committed when the transient is dropped, so clean up afterwards
+				for bracket in count..old_count {
+					<BracketIndices<T, I>>::remove((mode, bracket));
+				}
+			}
+		}
+
+		<BracketsCount<T, I>>::put(count);
+
+		Self::deposit_event(Event::BracketsCountSet(count));
+		moved
+	}
+
+	/// Weight of changing the amount of brackets, every player of the removed brackets and
+	/// the new top bracket of each game mode may be moved.
+	fn brackets_count_weight(count: u8) -> Weight {
+		let brackets = Self::brackets_count().saturating_sub(count).saturating_add(1) as Weight;
+		let players = brackets
+			.saturating_mul(T::MaxQueueSize::get().into())
+			.saturating_mul(T::GameModes::get().len() as Weight);
+		// weights need to be adjusted
+		T::DbWeight::get()
+			.reads_writes(3, 2)
+			.saturating_add(T::DbWeight::get().reads_writes(4, 6).saturating_mul(players))
+	}
+
+	/// Reserve the queue deposit from each player, from all of them or none.
+	fn reserve_deposits(players: &[T::AccountId]) -> DispatchResult {
+		let deposit = T::QueueDeposit::get();
+		if deposit.is_zero() {
+			return Ok(())
+		}
+
+		// a deposit is bound to a single match, until the game pallet releases or slashes it
+		ensure!(
+			!players.iter().any(|player| <Deposits<T, I>>::contains_key(player)),
+			Error::<T, I>::DepositHeld
+		);
+
+		let mut reserved: Vec<&T::AccountId> = Vec::new();
+		for player in players {
+			if T::Currency::reserve(player, deposit).is_err() {
+				for player in reserved {
+					T::Currency::unreserve(player, deposit);
+				}
+				return Err(Error::<T, I>::InsufficientDeposit)?
+			}
+			reserved.push(player);
+		}
+		for player in reserved {
+			<Deposits<T, I>>::insert(player, deposit);
+		}
+		Ok(())
+	}
+
+	/// Return the queue deposits of all players queued by an account.
+	fn release_deposits(account: &T::AccountId) {
+		for member in Self::party_members(account) {
+			Self::do_release_deposit(&member);
+		}
+	}
+
+	fn do_release_deposit(account: &T::AccountId) {
+		if let Some(deposit) = <Deposits<T, I>>::take(account) {
+			T::Currency::unreserve(account, deposit);
+		}
+	}
+
+	fn do_slash_deposit(account: &T::AccountId) {
+		if let Some(deposit) = <Deposits<T, I>>::take(account) {
+			let (_, remaining) = T::Currency::slash_reserved(account, deposit);
+			Self::deposit_event(Event::DepositSlashed(
+				account.clone(),
+				deposit.saturating_sub(remaining),
+			));
+		}
+	}
+
+	fn do_penalise(account: T::AccountId) {
+		let rating = Self::ratings(&account).saturating_sub(T::DeclinePenalty::get());
+		<Ratings<T, I>>::insert(&account, rating);
+
+		Self::deposit_event(Event::RatingChanged(account, rating));
+	}
+
+	fn do_expire_matches(now: T::BlockNumber) -> Weight {
+		// initial weights
+		let mut tot_weights = 10_000;
+
+		for (match_id, mode) in <MatchDeadlines<T, I>>::drain_prefix(now) {
+			// matches all players accepted wait to be handed out
+			let waiting = Self::pending_matches(mode, match_id).map_or(0, |m| m.waiting);
+			if waiting > 0 {
+				Self::deposit_event(Event::MatchExpired(match_id));
+				tot_weights = tot_weights + Self::do_cancel_match(mode, match_id, None);
+			}
+			// weights need to be adjusted
+			tot_weights = tot_weights + T::DbWeight::get().reads_writes(2, 1);
+		}
+
+		tot_weights
+	}
+
+	/// Return whether a unit finds a slot for each of its players and doesn't clash with
+	/// the units already selected.
+	fn fits(
+		selected: &[(Bracket, usize)],
+		free_slots: u8,
+		candidate: (Bracket, usize),
+		size: u8,
+		clashes: Clashes,
+	) -> bool {
+		size <= free_slots && !selected.iter().any(|unit| clashes(unit, &candidate))
+	}
+
+	/// Take a unit if it fits, return true once all slots are filled.
+	fn fill_slots(
+		selected: &mut Vec<(Bracket, usize)>,
+		free_slots: &mut u8,
+		bracket: usize,
+		unit: usize,
+		size: u8,
+		clashes: Clashes,
+	) -> bool {
+		let candidate = (bracket as Bracket, unit);
+		if Self::fits(selected, *free_slots, candidate, size, clashes) {
+			selected.push(candidate);
+			*free_slots -= size;
+		}
+		*free_slots == 0
+	}
+
+	/// Fill the match by draining the brackets in order, starting from the first bracket
+	/// able to fill it.
+	fn simple_brackets(
+		units: &[Vec<u8>],
+		max_players: u8,
+		min_players: u8,
+		timed_out: &[Vec<bool>],
+		clashes: Clashes,
+	) -> Vec<(Bracket, usize)> {
+		for start in 0..units.len() {
 			// skip if bracket is empty
-			if queue.size(i) == 0 {
+			if units[start].is_empty() {
 				continue
 			}
-			// iterate for each slot occupied and fill, till player match size reached
-			for _j in 0..queue.size(i) {
-				if brackets.len() == max_players as usize {
-					break
+			let mut selected: Vec<(Bracket, usize)> = Vec::new();
+			let mut free_slots = max_players;
+			// pass trough all following brackets
+			for (i, sizes) in units.iter().enumerate().skip(start) {
+				// first fit each unit, till player match size reached
+				for (j, size) in sizes.iter().enumerate() {
+					if Self::fill_slots(&mut selected, &mut free_slots, i, j, *size, clashes) {
+						return selected
+					}
 				}
-				brackets.push(i);
 			}
-			// leave if brackets is filled with brackets
-			if brackets.len() == max_players as usize {
-				break
+			let selected =
+				Self::enough_players(selected, free_slots, max_players, min_players, timed_out);
+			if !selected.is_empty() {
+				return selected
 			}
 		}
-		// vec not filled with enough brackets leave
-		if brackets.len() < max_players as usize {
-			return result
+		Vec::new()
+	}
+
+	/// Fill the match from the first bracket with enough players queued within its allowed
+	/// distance, closer brackets are drained first.
+	fn same_brackets(
+		units: &[Vec<u8>],
+		distances: &[Bracket],
+		max_players: u8,
+		min_players: u8,
+		timed_out: &[Vec<bool>],
+		clashes: Clashes,
+	) -> Vec<(Bracket, usize)> {
+		for (i, distance) in distances.iter().enumerate() {
+			// skip if bracket is empty
+			if units[i].is_empty() {
+				continue
+			}
+			let mut selected: Vec<(Bracket, usize)> = Vec::new();
+			let mut free_slots = max_players;
+			let max_distance = units.len().min(*distance as usize);
+			for d in 0..=max_distance {
+				// lower bracket first, then upper bracket of the same distance
+				let mut candidates: Vec<usize> = Vec::new();
+				if d <= i {
+					candidates.push(i - d);
+				}
+				if d > 0 && i + d < units.len() {
+					candidates.push(i + d);
+				}
+				for c in candidates {
+					for (j, size) in units[c].iter().enumerate() {
+						if Self::fill_slots(&mut selected, &mut free_slots, c, j, *size, clashes) {
+							return selected
+						}
+					}
+				}
+			}
+			let selected =
+				Self::enough_players(selected, free_slots, max_players, min_players, timed_out);
+			if !selected.is_empty() {
+				return selected
+			}
 		}
+		Vec::new()
+	}
 
-		// pop from the harvested brackets players
-		for i in 0..brackets.len() {
-			if let Some(p) = queue.pop(brackets[i]) {
-				result.push(p.account.clone());
-				Self::deposit_event(Event::Popped(p));
+	/// Fill the match with the first fitting unit of each bracket in order, starting from
+	/// the first bracket able to fill it.
+	fn mix_brackets(
+		units: &[Vec<u8>],
+		max_players: u8,
+		min_players: u8,
+		timed_out: &[Vec<bool>],
+		clashes: Clashes,
+	) -> Vec<(Bracket, usize)> {
+		for start in 0..units.len() {
+			// skip if bracket is empty
+			if units[start].is_empty() {
+				continue
+			}
+			let mut selected: Vec<(Bracket, usize)> = Vec::new();
+			let mut free_slots = max_players;
+			for (i, sizes) in units.iter().enumerate().skip(start) {
+				// skip if no unit of the bracket fits
+				let fitting = (0..sizes.len()).find(|j| {
+					Self::fits(&selected, free_slots, (i as Bracket, *j), sizes[*j], clashes)
+				});
+				if let Some(j) = fitting {
+					if Self::fill_slots(&mut selected, &mut free_slots, i, j, sizes[j], clashes) {
+						return selected
+					}
+				}
+			}
+			let selected =
+				Self::enough_players(selected, free_slots, max_players, min_players, timed_out);
+			if !selected.is_empty() {
+				return selected
 			}
 		}
-		// return result
-		result
+		Vec::new()
+	}
+
+	/// Return the units of a match not filled up, if they are at least the minimum of players
+	/// and one of them waited the fill timeout.
+	fn enough_players(
+		selected: Vec<(Bracket, usize)>,
+		free_slots: u8,
+		max_players: u8,
+		min_players: u8,
+		timed_out: &[Vec<bool>],
+	) -> Vec<(Bracket, usize)> {
+		let waited = selected.iter().any(|(bracket, unit)| timed_out[*bracket as usize][*unit]);
+		if min_players > 0 && max_players - free_slots >= min_players && waited {
+			return selected
+		}
+		Vec::new()
+	}
+
+	/// Return the allowed bracket distance of a player, growing with the blocks waited.
+	fn widened_distance(queued_at: T::BlockNumber) -> Bracket {
+		let period = T::WideningPeriod::get();
+		if period.is_zero() {
+			return 0
+		}
+
+		let waited = <frame_system::Pallet<T>>::block_number().saturating_sub(queued_at);
+		(waited / period).saturated_into::<Bracket>()
 	}
 
 	fn do_is_queued(account: T::AccountId) -> bool {
-		Self::queue_transient().is_queued(account)
+		// players waiting to accept a match are still queued
+		<PendingPlayers<T, I>>::contains_key(&account) ||
+			<BracketKeyIndexMap<T, I>>::contains_key(Self::party_leader(account))
+	}
+
+	fn do_bracket_of(account: T::AccountId) -> Option<Bracket> {
+		Self::key_index(Self::party_leader(account)).map(|(_, bracket, _)| bracket)
 	}
 
-	fn do_queue_size(bracket: u8) -> BufferIndex {
-		Self::queue_transient().size(bracket)
+	fn do_queue_size(mode: GameModeId, bracket: u8) -> Result<BufferIndex, DispatchError> {
+		Self::ensure_queue(mode, bracket)?;
+		Ok(Self::queue_transient(mode).size(bracket))
 	}
 
-	fn do_all_queue_size() -> BufferIndex {
-		let queue = Self::queue_transient();
+	fn do_all_queue_size(mode: GameModeId) -> Result<BufferIndex, DispatchError> {
+		Self::game_mode(mode)?;
+
+		let queue = Self::queue_transient(mode);
 
 		let mut total_queued: BufferIndex = 0;
 		// count all existing brackets
@@ -242,59 +1818,264 @@ impl<T: Config<I>, I: 'static> Pallet<T, I> {
 			total_queued = total_queued + queue.size(i);
 		}
 		// return result
-		total_queued
+		Ok(total_queued)
+	}
+}
+
+impl<T: Config<I>, I: 'static> Pallet<T, I> {
+	/// Return the queue size of each bracket of a game mode, used by the runtime api.
+	pub fn bracket_sizes(mode: GameModeId) -> Vec<BufferIndex> {
+		(0..Self::brackets_count())
+			.map(|bracket| Self::do_queue_size(mode, bracket).unwrap_or_default())
+			.collect()
+	}
+
+	/// Return the total amount of queued players of a game mode, used by the runtime api.
+	pub fn total_queued(mode: GameModeId) -> BufferIndex {
+		Self::do_all_queue_size(mode).unwrap_or_default()
+	}
+
+	/// Return the game mode, bracket and position in the bracket queue of an account, used
+	/// by the runtime api. The position counts the players queued ahead, party members share
+	/// the position of their party and players waiting to accept a match are at the front.
+	pub fn queue_position(account: T::AccountId) -> Option<(GameModeId, Bracket, BufferIndex)> {
+		let leader = Self::party_leader(account.clone());
+		if let Some((mode, match_id, _)) = Self::pending_players(&account) {
+			let (bracket, _) = <PendingUnits<T, I>>::iter_prefix_values(match_id)
+				.find(|(_, p)| p.account == leader)?;
+			return Some((mode, bracket, 0))
+		}
+
+		let (mode, bracket, index) = Self::key_index(&leader)?;
+		let (start, _) = Self::indices((mode, bracket));
+		// parties ahead take a slot for each of their players
+		let position = (0..index.wrapping_sub(start))
+			.map(|i| {
+				let ahead = Self::index_key((mode, bracket), start.wrapping_add(i));
+				Self::key_value((mode, bracket), ahead).party_size.max(1) as BufferIndex
+			})
+			.fold(0, |position: BufferIndex, size| position.saturating_add(size));
+		Some((mode, bracket, position))
+	}
+
+	/// Return the estimated blocks until a queued account is matched, used by the runtime
+	/// api. Based on the average blocks between the recent matches of its game mode.
+	pub fn estimated_wait(account: T::AccountId) -> Option<T::BlockNumber> {
+		// players waiting to accept a match are matched already
+		if <PendingPlayers<T, I>>::contains_key(&account) {
+			return Some(Zero::zero())
+		}
+
+		let (mode, _, position) = Self::queue_position(account)?;
+		let (last_match, interval) = Self::match_throughput(mode);
+		// no estimate before the first match
+		if last_match.is_zero() {
+			return None
+		}
+
+		let players = Self::game_mode(mode).ok()?.max_players.max(1);
+		let matches_ahead = (position / players as BufferIndex) as u32;
+		Some(interval.saturating_mul((matches_ahead + 1).into()))
 	}
 }
 
 impl<T: Config> MatchFunc<T::AccountId> for Pallet<T> {
-	fn empty_queue(bracket: u8) {
-		Self::do_empty_queue(bracket);
+	fn empty_queue(mode: GameModeId, bracket: u8) -> DispatchResult {
+		Self::do_empty_queue(mode, bracket)
+	}
+
+	fn all_empty_queue(mode: GameModeId) -> DispatchResult {
+		Self::do_all_empty_queue(mode)
+	}
+
+	fn all_empty_queue_weight() -> Weight {
+		Self::do_all_empty_queue_weight()
+	}
+
+	fn match_size(mode: GameModeId) -> Result<(u8, u8), DispatchError> {
+		let game_mode = Self::game_mode(mode)?;
+		Ok((game_mode.min_players, game_mode.max_players))
+	}
+
+	fn party_leader(account: T::AccountId) -> T::AccountId {
+		Self::party_of(&account).unwrap_or(account)
+	}
+
+	fn party_members(account: T::AccountId) -> Vec<T::AccountId> {
+		Self::party_members(&account)
+	}
+
+	fn add_queue(account: T::AccountId, mode: GameModeId, bracket: u8) -> DispatchResult {
+		Self::do_add_queue(account, mode, bracket)
 	}
 
-	fn all_empty_queue() {
-		Self::do_all_empty_queue();
+	fn add_queue_by_rating(account: T::AccountId, mode: GameModeId) -> DispatchResult {
+		Self::do_add_queue_by_rating(account, mode)
 	}
 
-	fn add_queue(account: T::AccountId, bracket: u8) -> bool {
-		Self::do_add_queue(account, bracket)
+	fn requeue(account: T::AccountId) -> DispatchResult {
+		Self::do_requeue(account)
 	}
 
-	fn try_match() -> Vec<T::AccountId> {
-		Self::do_try_match()
+	fn leave_queue(account: T::AccountId) -> DispatchResult {
+		Self::do_leave_queue(account)
+	}
+
+	fn leave_queue_weight() -> Weight {
+		Self::do_leave_queue_weight()
+	}
+
+	fn report_result(account: T::AccountId, opponent: T::AccountId, result: MatchResult) {
+		Self::do_report_result(account, opponent, result);
+	}
+
+	fn rating(account: T::AccountId) -> Rating {
+		Self::ratings(account)
+	}
+
+	fn try_match(mode: GameModeId) -> Result<Vec<T::AccountId>, DispatchError> {
+		Self::do_try_match(mode)
 	}
 
 	fn is_queued(account: T::AccountId) -> bool {
 		Self::do_is_queued(account)
 	}
 
-	fn queue_size(bracket: u8) -> BufferIndex {
-		Self::do_queue_size(bracket)
+	fn ensure_not_queued(account: T::AccountId) -> DispatchResult {
+		ensure!(!Self::do_is_queued(account), Error::<T>::AlreadyQueued);
+		Ok(())
 	}
 
-	fn all_queue_size() -> BufferIndex {
-		Self::do_all_queue_size()
+	fn bracket_of(account: T::AccountId) -> Option<u8> {
+		Self::do_bracket_of(account)
+	}
+
+	fn queue_size(mode: GameModeId, bracket: u8) -> Result<BufferIndex, DispatchError> {
+		Self::do_queue_size(mode, bracket)
+	}
+
+	fn all_queue_size(mode: GameModeId) -> Result<BufferIndex, DispatchError> {
+		Self::do_all_queue_size(mode)
+	}
+
+	fn release_deposit(account: T::AccountId) {
+		Self::do_release_deposit(&account)
+	}
+
+	fn slash_deposit(account: T::AccountId) {
+		Self::do_slash_deposit(&account)
+	}
+}
+
+/// Handler of the matches the matchmaker creates in its own hook.
+///
+/// Handlers of a tuple are offered a match in order, until one of them takes it.
+pub trait OnMatched<AccountId> {
+	/// Start a match of a game mode, returns whether the match was taken and the weight
+	/// consumed.
+	///
+	/// The match id is the one of the `Matched` event, to refer to the same match.
+	fn on_matched(mode: GameModeId, match_id: MatchId, players: Vec<AccountId>) -> (bool, Weight);
+
+	/// Return whether a match of a game mode can be started now, players stay queued until
+	/// it can.
+	fn has_capacity(_mode: GameModeId) -> bool {
+		true
+	}
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(5)]
+impl<AccountId: Clone> OnMatched<AccountId> for Tuple {
+	fn on_matched(mode: GameModeId, match_id: MatchId, players: Vec<AccountId>) -> (bool, Weight) {
+		let mut weight: Weight = 0;
+		for_tuples!( #(
+			let (taken, handler_weight) = Tuple::on_matched(mode, match_id, players.clone());
+			weight = weight.saturating_add(handler_weight);
+			if taken {
+				return (true, weight)
+			}
+		)* );
+		(false, weight)
+	}
+
+	fn has_capacity(mode: GameModeId) -> bool {
+		for_tuples!( #(
+			if !Tuple::has_capacity(mode) {
+				return false
+			}
+		)* );
+		true
 	}
 }
 
 pub trait MatchFunc<AccountId> {
-	/// empty specific bracket queue
-	fn empty_queue(bracket: u8);
+	/// empty specific bracket queue of a game mode, fails if the queue does not exist
+	fn empty_queue(mode: GameModeId, bracket: u8) -> DispatchResult;
 
-	/// empty all queues
-	fn all_empty_queue();
+	/// empty all bracket queues of a game mode and drop its pending matches, fails if the
+	/// game mode does not exist
+	fn all_empty_queue(mode: GameModeId) -> DispatchResult;
 
-	/// return true if adding account to bracket queue was successful
-	fn add_queue(account: AccountId, bracket: u8) -> bool;
+	/// return the maximum weight of `all_empty_queue`
+	fn all_empty_queue_weight() -> Weight;
 
-	/// try create a match
-	fn try_match() -> Vec<AccountId>;
+	/// return the minimum and maximum amount of players of a match of a game mode, fails if
+	/// the game mode does not exist
+	fn match_size(mode: GameModeId) -> Result<(u8, u8), DispatchError>;
 
-	// return true if an account is queued in any bracket
+	/// return the leader of the party of an account, the account itself if it has no party
+	fn party_leader(account: AccountId) -> AccountId;
+
+	/// return the players an account queues, its whole party if it is a party leader
+	fn party_members(account: AccountId) -> Vec<AccountId>;
+
+	/// add account to bracket queue of a game mode, fails if the queue does not exist, the
+	/// account is already queued in any game mode or the queue is full
+	fn add_queue(account: AccountId, mode: GameModeId, bracket: u8) -> DispatchResult;
+
+	/// add account to the bracket queue of its rating, fails like `add_queue`
+	fn add_queue_by_rating(account: AccountId, mode: GameModeId) -> DispatchResult;
+
+	/// send the unit of an account back to the front of its bracket queue with the block it
+	/// queued at, fails if the account doesn't lead a unit of the match handed to `OnMatched`
+	fn requeue(account: AccountId) -> DispatchResult;
+
+	/// remove account from its bracket queue, fails if the account is not queued
+	fn leave_queue(account: AccountId) -> DispatchResult;
+
+	/// return the maximum weight of `leave_queue`, that shifts every unit queued behind the
+	/// account
+	fn leave_queue_weight() -> Weight;
+
+	/// update ratings of both accounts with the result of a finished match
+	fn report_result(account: AccountId, opponent: AccountId, result: MatchResult);
+
+	/// return the current rating of an account
+	fn rating(account: AccountId) -> Rating;
+
+	/// try create a match of a game mode, fails if the game mode does not exist or its
+	/// queues are empty or hold not enough players, with an accept period only matches all
+	/// players accepted are returned
+	fn try_match(mode: GameModeId) -> Result<Vec<AccountId>, DispatchError>;
+
+	// return true if an account is queued in any bracket of any game mode
 	fn is_queued(account: AccountId) -> bool;
 
-	// return size of a specific bracket queue
-	fn queue_size(bracket: u8) -> BufferIndex;
+	/// fails with `AlreadyQueued` if an account is queued in any bracket of any game mode
+	fn ensure_not_queued(account: AccountId) -> DispatchResult;
+
+	// return the bracket an account is queued in
+	fn bracket_of(account: AccountId) -> Option<u8>;
+
+	// return size of a specific bracket queue of a game mode, fails if the queue does not exist
+	fn queue_size(mode: GameModeId, bracket: u8) -> Result<BufferIndex, DispatchError>;
+
+	// return total size of all queued accounts in all brackets of a game mode
+	fn all_queue_size(mode: GameModeId) -> Result<BufferIndex, DispatchError>;
+
+	/// return the queue deposit of an account, once its match completed
+	fn release_deposit(account: AccountId);
 
-	// return total size of all queued accounts in all brackets
-	fn all_queue_size() -> BufferIndex;
+	/// slash the queue deposit of an account, that was matched but never acted on its match
+	fn slash_deposit(account: AccountId);
 }
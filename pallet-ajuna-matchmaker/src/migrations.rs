@@ -0,0 +1,125 @@
+use super::*;
+use frame_support::{
+	storage::migration::{put_storage_value, storage_iter, take_storage_value},
+	traits::{GetStorageVersion, PalletInfoAccess, StorageVersion},
+	Blake2_128Concat, StorageHasher,
+};
+
+pub mod v1 {
+	use super::*;
+
+	/// Return the bracket of a key before version 1, that starts with the `Blake2_128Concat`
+	/// hash of the bracket instead of the hash of the game mode and bracket.
+	fn old_bracket(key: &[u8]) -> Option<Bracket> {
+		let bracket = *key.get(16)?;
+		if Blake2_128Concat::hash(&[bracket])[..] == key[..17] {
+			return Some(bracket)
+		}
+		None
+	}
+
+	/// Move the entries of a storage item keyed by bracket into the same bracket of game
+	/// mode 0, the only game mode before version 1. Keys that follow the bracket are kept.
+	fn rekey_item<T: Config<I>, I: 'static, V: Decode + Encode>(item: &[u8]) -> Weight {
+		let pallet = <Pallet<T, I> as PalletInfoAccess>::name().as_bytes();
+		let old: Vec<(Bracket, Vec<u8>, V)> = storage_iter::<V>(pallet, item)
+			.filter_map(|(key, value)| old_bracket(&key).map(|bracket| (bracket, key, value)))
+			.collect();
+
+		let moved = old.len() as Weight;
+		for (bracket, key, value) in old {
+			let _ = take_storage_value::<V>(pallet, item, &key);
+			let queue_key: QueueKey = (0, bracket);
+			let new_key =
+				[Blake2_128Concat::hash(&queue_key.encode()), key[17..].to_vec()].concat();
+			put_storage_value(pallet, item, &new_key, value);
+		}
+
+		T::DbWeight::get().reads_writes(moved, 2 * moved)
+	}
+
+	/// Key the bracket queues by game mode and bracket, added in version 1.
+	fn rekey_queues<T: Config<I>, I: 'static>() -> Weight {
+		rekey_item::<T, I, (BufferIndex, BufferIndex)>(b"BracketIndices")
+			.saturating_add(rekey_item::<T, I, T::AccountId>(b"BracketIndexKeyMap"))
+			// the player structure before version 1 encodes as its account
+			.saturating_add(rekey_item::<T, I, T::AccountId>(b"BracketKeyValueMap"))
+	}
+
+	/// Queued player before version 1, that only stored its account.
+	#[derive(Decode)]
+	struct OldPlayerStruct<AccountId> {
+		account: AccountId,
+	}
+
+	/// Extend the queued players by the fields added in version 1.
+	///
+	/// The enqueue block wasn't stored before, so the wait of players already queued is
+	/// counted from the runtime upgrade on.
+	fn translate_players<T: Config<I>, I: 'static>() -> Weight {
+		let now = <frame_system::Pallet<T>>::block_number();
+		let mut translated: Weight = 0;
+		<BracketKeyValueMap<T, I>>::translate_values(|old: OldPlayerStruct<T::AccountId>| {
+			translated += 1;
+			Some(PlayerStruct {
+				account: old.account,
+				queued_at: now,
+				party_size: 1,
+				attributes: Default::default(),
+			})
+		});
+
+		T::DbWeight::get().reads_writes(translated, translated)
+	}
+
+	/// Build the reverse index of the queued accounts, added in version 1, from the slots
+	/// of the brackets.
+	fn index_accounts<T: Config<I>, I: 'static>() -> Weight {
+		let mut indexed: Weight = 0;
+		for ((mode, bracket), index, account) in <BracketIndexKeyMap<T, I>>::iter() {
+			<BracketKeyIndexMap<T, I>>::insert(account, (mode, bracket, index));
+			indexed += 1;
+		}
+
+		T::DbWeight::get().reads_writes(indexed, indexed)
+	}
+
+	/// Migrate the queued players and brackets to version 1.
+	///
+	/// Before version 1 the amount of brackets always followed `AmountBrackets`, so queues
+	/// of brackets a runtime upgrade removed were left behind. The amount of brackets in use
+	/// is restored from the bracket bounds and then re-tuned to `AmountBrackets`. The queues
+	/// become the queues of game mode 0, queued players only stored their account and get
+	/// the fields added since, their accounts are indexed to the slot they are queued in.
+	pub fn migrate<T: Config<I>, I: 'static>() -> Weight {
+		let on_chain_version = Pallet::<T, I>::on_chain_storage_version();
+		if on_chain_version >= 1 {
+			return T::DbWeight::get().reads(1)
+		}
+
+		let players = rekey_queues::<T, I>()
+			.saturating_add(translate_players::<T, I>())
+			.saturating_add(index_accounts::<T, I>());
+
+		let mut reads: Weight = 1;
+		let mut brackets_count: u8 = 0;
+		for (_, bracket) in <BracketIndices<T, I>>::iter_keys() {
+			reads += 1;
+			brackets_count = brackets_count.max(bracket.saturating_add(1));
+		}
+
+		let mut writes: Weight = 2;
+		if brackets_count > 0 {
+			<BracketsCount<T, I>>::put(brackets_count);
+			writes += 1;
+		}
+		let moved = Pallet::<T, I>::do_set_brackets_count(T::AmountBrackets::get().max(1));
+
+		StorageVersion::new(1).put::<Pallet<T, I>>();
+
+		// weights need to be adjusted
+		players
+			.saturating_add(T::DbWeight::get().reads_writes(reads, writes))
+			.saturating_add(T::DbWeight::get().reads_writes(moved as Weight, 4 * moved as Weight))
+	}
+}
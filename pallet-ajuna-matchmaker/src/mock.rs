@@ -1,11 +1,15 @@
 use crate as pallet_matchmaker;
-use frame_support::parameter_types;
+use frame_support::{parameter_types, weights::Weight};
 use frame_system as system;
+use pallet_matchmaker::{
+	GameModeId, GameModeSettings, MatchFilter, MatchId, MatchingType, OnMatched, PlayerStruct,
+};
 use sp_core::H256;
 use sp_runtime::{
 	testing::Header,
 	traits::{BlakeTwo256, IdentityLookup},
 };
+use std::cell::RefCell;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
@@ -18,6 +22,7 @@ frame_support::construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Event<T>},
 		MatchMaker: pallet_matchmaker::{Pallet, Call, Storage, Event<T>},
 	}
 );
@@ -45,7 +50,7 @@ impl system::Config for Test {
 	type BlockHashCount = BlockHashCount;
 	type Version = ();
 	type PalletInfo = PalletInfo;
-	type AccountData = ();
+	type AccountData = pallet_balances::AccountData<u64>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
@@ -54,14 +59,107 @@ impl system::Config for Test {
 }
 
 parameter_types! {
-	pub const AmountPlayers: u8 = 2;
+	pub const ExistentialDeposit: u64 = 1;
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ();
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type Balance = u64;
+	type Event = Event;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub GameModes: Vec<GameModeSettings<u64>> = vec![
+		GameModeSettings { min_players: 2, max_players: 2, fill_timeout: 0 },
+		GameModeSettings { min_players: 3, max_players: 3, fill_timeout: 0 },
+		GameModeSettings { min_players: 2, max_players: 4, fill_timeout: 10 },
+	];
 	pub const AmountBrackets: u8 = 3;
+	pub const DefaultRating: u16 = 1500;
+	pub const RatingFactor: u16 = 32;
+	pub BracketThresholds: Vec<u16> = vec![1400, 1600];
+	pub const DefaultMatchingType: MatchingType = MatchingType::Simple;
+	pub const WideningPeriod: u64 = 10;
+	pub const QueueTimeToLive: u64 = 50;
+	pub const MaxExpiriesPerBlock: u32 = 2;
+	pub const MaxQueueSize: u16 = 8;
+	pub const MaxPartySize: u32 = 3;
+	pub storage AcceptPeriod: u64 = 0;
+	pub const DeclinePenalty: u16 = 16;
+	pub storage QueueDeposit: u64 = 0;
+	pub const MaxRecentOpponents: u32 = 4;
+	pub const RematchWindow: u64 = 20;
+	pub storage MaxMatchesPerBlock: u32 = 0;
+	pub storage HandlerFull: bool = false;
+}
+
+/// Only players of the same region, their attribute, are matched together.
+pub struct SameRegion;
+impl MatchFilter<PlayerStruct<u64, u64, u8>> for SameRegion {
+	fn compatible(
+		player: &PlayerStruct<u64, u64, u8>,
+		candidate: &PlayerStruct<u64, u64, u8>,
+	) -> bool {
+		player.attributes == candidate.attributes
+	}
+}
+
+thread_local! {
+	static MATCHES: RefCell<Vec<(GameModeId, Vec<u64>)>> = RefCell::new(Vec::new());
+}
+
+/// Keep the matches the matchmaker created in its hook.
+pub struct RecordMatches;
+impl OnMatched<u64> for RecordMatches {
+	fn on_matched(mode: GameModeId, _match_id: MatchId, players: Vec<u64>) -> (bool, Weight) {
+		// matches of the last game mode are left to no one
+		if mode == 2 {
+			return (false, 0)
+		}
+
+		MATCHES.with(|matches| matches.borrow_mut().push((mode, players)));
+		(true, 0)
+	}
+
+	fn has_capacity(_mode: GameModeId) -> bool {
+		!HandlerFull::get()
+	}
+}
+
+pub fn matches() -> Vec<(GameModeId, Vec<u64>)> {
+	MATCHES.with(|matches| matches.borrow().clone())
 }
 
 impl pallet_matchmaker::Config for Test {
 	type Event = Event;
-	type AmountPlayers = AmountPlayers;
+	type GameModes = GameModes;
 	type AmountBrackets = AmountBrackets;
+	type DefaultRating = DefaultRating;
+	type RatingFactor = RatingFactor;
+	type BracketThresholds = BracketThresholds;
+	type DefaultMatchingType = DefaultMatchingType;
+	type WideningPeriod = WideningPeriod;
+	type QueueTimeToLive = QueueTimeToLive;
+	type MaxExpiriesPerBlock = MaxExpiriesPerBlock;
+	type MaxQueueSize = MaxQueueSize;
+	type MaxPartySize = MaxPartySize;
+	type AcceptPeriod = AcceptPeriod;
+	type DeclinePenalty = DeclinePenalty;
+	type Currency = Balances;
+	type QueueDeposit = QueueDeposit;
+	type MaxRecentOpponents = MaxRecentOpponents;
+	type RematchWindow = RematchWindow;
+	type PlayerAttributes = u8;
+	type MatchFilter = SameRegion;
+	type MaxMatchesPerBlock = MaxMatchesPerBlock;
+	type OnMatched = RecordMatches;
+	type AdminOrigin = frame_system::EnsureRoot<u64>;
 }
 
 // Build genesis storage according to the mock runtime.
@@ -0,0 +1,89 @@
+//! # Matchmaker Rating (based on the Elo rating system)
+//!
+//! This module provides the rating calculation the matchmaker uses to place players
+//! into ranking brackets. Ratings of both players are updated on each reported match
+//! result, the expected score is approximated by a lookup table to stay in integer
+//! arithmetic.
+use crate::brackets::Bracket;
+use codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+
+pub type Rating = u16;
+
+/// Result of a match from the point of view of the first player.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, TypeInfo, MaxEncodedLen)]
+pub enum MatchResult {
+	Won,
+	Draw,
+	Lost,
+}
+
+/// Score of a won match, a draw scores half of it and a lost match nothing.
+const SCORE_SCALE: i32 = 1000;
+
+/// Rating difference between two entries of the expected score table.
+const EXPECTED_SCORE_STEP: i32 = 50;
+
+/// Expected score of the higher rated player, for rating differences of 0, 50, .. 800.
+const EXPECTED_SCORE: [i32; 17] =
+	[500, 571, 640, 703, 760, 808, 849, 882, 909, 930, 947, 960, 969, 977, 983, 987, 990];
+
+/// Return the expected score of a player against an opponent, scaled by `SCORE_SCALE`.
+pub fn expected_score(rating: Rating, opponent: Rating) -> i32 {
+	let diff = rating as i32 - opponent as i32;
+	let abs_diff = diff.abs();
+
+	// interpolate linear between the two nearest table entries
+	let index = (abs_diff / EXPECTED_SCORE_STEP) as usize;
+	let expected = if index + 1 < EXPECTED_SCORE.len() {
+		let rest = abs_diff % EXPECTED_SCORE_STEP;
+		EXPECTED_SCORE[index] +
+			(EXPECTED_SCORE[index + 1] - EXPECTED_SCORE[index]) * rest / EXPECTED_SCORE_STEP
+	} else {
+		EXPECTED_SCORE[EXPECTED_SCORE.len() - 1]
+	};
+
+	if diff < 0 {
+		SCORE_SCALE - expected
+	} else {
+		expected
+	}
+}
+
+/// Return the new ratings of a player and the opponent after a match.
+///
+/// The factor is the maximum rating change of a single match.
+pub fn rate(
+	rating: Rating,
+	opponent: Rating,
+	result: &MatchResult,
+	factor: Rating,
+) -> (Rating, Rating) {
+	let score = match result {
+		MatchResult::Won => SCORE_SCALE,
+		MatchResult::Draw => SCORE_SCALE / 2,
+		MatchResult::Lost => 0,
+	};
+
+	// rating change is zero sum, what one player gains the other loses
+	let change = factor as i32 * (score - expected_score(rating, opponent)) / SCORE_SCALE;
+
+	(apply_change(rating, change), apply_change(opponent, -change))
+}
+
+/// Return the bracket of a rating, thresholds are expected in ascending order.
+///
+/// Each threshold reached moves the rating one bracket up, capped by the brackets count.
+pub fn bracket(rating: Rating, thresholds: &[Rating], brackets_count: Bracket) -> Bracket {
+	let reached = thresholds.iter().filter(|t| rating >= **t).count();
+
+	let max_bracket = brackets_count.saturating_sub(1);
+	if reached > max_bracket as usize {
+		return max_bracket
+	}
+	reached as Bracket
+}
+
+fn apply_change(rating: Rating, change: i32) -> Rating {
+	(rating as i32 + change).max(0).min(Rating::MAX as i32) as Rating
+}
@@ -1,15 +1,30 @@
-use crate::mock::*;
+use crate::{
+	brackets::{BracketsError, BufferIndex},
+	migrations,
+	mock::*,
+	BracketIndexKeyMap, BracketIndices, BracketsCount, DequeError, Error, MatchFunc, MatchResult,
+	MatchingStrategy, MatchingType, PendingPlayers, PlayerStruct, Ratings, StorageDeque,
+};
+use codec::Encode;
+use frame_support::{
+	assert_err, assert_ok,
+	error::BadOrigin,
+	storage::migration::{get_storage_value, put_storage_value},
+	traits::{Currency, Get, GetStorageVersion, OnInitialize, ReservableCurrency, StorageVersion},
+	Blake2_128Concat, StorageHasher,
+};
+use std::collections::VecDeque;
 
 #[test]
 fn test_is_queued() {
 	new_test_ext().execute_with(|| {
 		let player1 = 1;
 
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
 		assert_eq!(MatchMaker::do_is_queued(player1), false);
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
 		assert_eq!(MatchMaker::do_is_queued(player1), true);
-		MatchMaker::do_empty_queue(0);
+		assert_ok!(MatchMaker::do_empty_queue(0, 0));
 		assert_eq!(MatchMaker::do_is_queued(player1), false);
 	});
 }
@@ -20,18 +35,18 @@ fn test_try_duplicate_queue() {
 		let player1 = 1;
 		let player2 = 2;
 
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
 		// try same bracket
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), false);
+		assert_err!(MatchMaker::do_add_queue(player1, 0, 0), Error::<Test>::AlreadyQueued);
 		// try other bracket
-		assert_eq!(MatchMaker::do_add_queue(player1, 1), false);
+		assert_err!(MatchMaker::do_add_queue(player1, 0, 1), Error::<Test>::AlreadyQueued);
 
-		assert_eq!(MatchMaker::do_add_queue(player2, 1), true);
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 1));
 		// try same bracket
-		assert_eq!(MatchMaker::do_add_queue(player2, 1), false);
+		assert_err!(MatchMaker::do_add_queue(player2, 0, 1), Error::<Test>::AlreadyQueued);
 		// try other bracket
-		assert_eq!(MatchMaker::do_add_queue(player2, 0), false);
+		assert_err!(MatchMaker::do_add_queue(player2, 0, 0), Error::<Test>::AlreadyQueued);
 	});
 }
 
@@ -41,23 +56,23 @@ fn test_add_queue() {
 		let player1 = 1;
 		let player2 = 2;
 
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
-		assert_eq!(MatchMaker::do_try_match().is_empty(), true);
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
-		assert_eq!(MatchMaker::do_queue_size(0), 1);
-		assert_eq!(MatchMaker::do_try_match().is_empty(), true);
-		assert_eq!(MatchMaker::do_add_queue(player2, 0), true);
-		assert_eq!(MatchMaker::do_queue_size(0), 2);
-		assert_eq!(MatchMaker::do_try_match(), [1, 2]);
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
-		assert_eq!(MatchMaker::do_try_match().is_empty(), true);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(1));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 0));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(2));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
+		assert!(MatchMaker::do_try_match(0).is_err());
 
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
-		assert_eq!(MatchMaker::do_add_queue(player2, 0), true);
-		assert_eq!(MatchMaker::do_queue_size(0), 2);
-		MatchMaker::do_empty_queue(0);
-		assert_eq!(MatchMaker::do_try_match().is_empty(), true);
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 0));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(2));
+		assert_ok!(MatchMaker::do_empty_queue(0, 0));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
 	});
 }
 
@@ -78,24 +93,1146 @@ fn test_brackets() {
 		let player5 = 5; // bracket: 1
 		let player6 = 6; // bracket: 2
 
-		assert_eq!(MatchMaker::do_queue_size(0), 0);
-		assert_eq!(MatchMaker::do_all_queue_size(), 0);
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
-		assert_eq!(MatchMaker::do_add_queue(player2, 0), true);
-		assert_eq!(MatchMaker::do_add_queue(player3, 0), true);
-		assert_eq!(MatchMaker::do_add_queue(player4, 1), true);
-		assert_eq!(MatchMaker::do_add_queue(player5, 1), true);
-		assert_eq!(MatchMaker::do_add_queue(player6, 2), true);
-		assert_eq!(MatchMaker::do_queue_size(0), 3);
-		assert_eq!(MatchMaker::do_queue_size(1), 2);
-		assert_eq!(MatchMaker::do_queue_size(2), 1);
-		assert_eq!(MatchMaker::do_all_queue_size(), 6);
-		assert_eq!(MatchMaker::do_try_match(), [1, 2]);
-		assert_eq!(MatchMaker::do_try_match(), [3, 4]);
-		assert_eq!(MatchMaker::do_add_queue(player1, 0), true);
-		assert_eq!(MatchMaker::do_try_match(), [1, 5]);
-		assert_eq!(MatchMaker::do_try_match().is_empty(), true);
-		assert_eq!(MatchMaker::do_add_queue(player5, 1), true);
-		assert_eq!(MatchMaker::do_try_match(), [5, 6]);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(0));
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player4, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(player5, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(player6, 0, 2));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(3));
+		assert_eq!(MatchMaker::do_queue_size(0, 1), Ok(2));
+		assert_eq!(MatchMaker::do_queue_size(0, 2), Ok(1));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(6));
+		// neighbour brackets meet once the first widening period passed
+		System::set_block_number(10);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![3, 4]));
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 5]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_ok!(MatchMaker::do_add_queue(player5, 0, 1));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![5, 6]));
+	});
+}
+
+#[test]
+fn test_leave_queue() {
+	new_test_ext().execute_with(|| {
+		let player1 = 1;
+		let player2 = 2;
+		let player3 = 3;
+		let player4 = 4;
+
+		assert_err!(MatchMaker::do_leave_queue(player1), Error::<Test>::NotQueued);
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player4, 0, 1));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(3));
+
+		// leave from the middle of the queue
+		assert_ok!(MatchMaker::do_leave_queue(player2));
+		assert_eq!(MatchMaker::do_is_queued(player2), false);
+		assert_err!(MatchMaker::do_leave_queue(player2), Error::<Test>::NotQueued);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(2));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(3));
+
+		// fifo order is kept after closing the gap
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 3]));
+
+		// leave from another bracket
+		assert_ok!(MatchMaker::do_leave_queue(player4));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(0));
+		assert!(MatchMaker::do_try_match(0).is_err());
+
+		// rejoin after leaving
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(player4, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 4]));
+	});
+}
+
+#[test]
+fn test_report_result() {
+	new_test_ext().execute_with(|| {
+		let player1 = 1;
+		let player2 = 2;
+		let player3 = 3;
+
+		// unrated players start with the default rating
+		assert_eq!(MatchMaker::ratings(player1), 1500);
+		assert_eq!(MatchMaker::ratings(player2), 1500);
+
+		// even ratings, winner takes half of the rating factor
+		MatchMaker::do_report_result(player1, player2, MatchResult::Won);
+		assert_eq!(MatchMaker::ratings(player1), 1516);
+		assert_eq!(MatchMaker::ratings(player2), 1484);
+
+		// higher rated player loses more against a lower rated player
+		MatchMaker::do_report_result(player1, player2, MatchResult::Lost);
+		assert_eq!(MatchMaker::ratings(player1), 1499);
+		assert_eq!(MatchMaker::ratings(player2), 1501);
+
+		// draw against an even opponent changes nothing
+		MatchMaker::do_report_result(player3, player3 + 1, MatchResult::Draw);
+		assert_eq!(MatchMaker::ratings(player3), 1500);
+		assert_eq!(MatchMaker::ratings(player3 + 1), 1500);
+	});
+}
+
+#[test]
+fn test_add_queue_by_rating() {
+	new_test_ext().execute_with(|| {
+		let player1 = 1; // rating: 1300, bracket: 0
+		let player2 = 2; // rating: 1500, bracket: 1
+		let player3 = 3; // rating: 1600, bracket: 2
+		let player4 = 4; // rating: 2500, bracket: 2
+
+		Ratings::<Test>::insert(player1, 1300);
+		Ratings::<Test>::insert(player3, 1600);
+		Ratings::<Test>::insert(player4, 2500);
+
+		assert_ok!(MatchMaker::do_add_queue_by_rating(player1, 0));
+		assert_ok!(MatchMaker::do_add_queue_by_rating(player2, 0));
+		assert_ok!(MatchMaker::do_add_queue_by_rating(player3, 0));
+		assert_ok!(MatchMaker::do_add_queue_by_rating(player4, 0));
+		assert_err!(MatchMaker::do_add_queue_by_rating(player4, 0), Error::<Test>::AlreadyQueued);
+
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(1));
+		assert_eq!(MatchMaker::do_queue_size(0, 1), Ok(1));
+		assert_eq!(MatchMaker::do_queue_size(0, 2), Ok(2));
+	});
+}
+
+#[test]
+fn test_matching_type_simple() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(MatchMaker::matching_type(), MatchingType::Simple);
+
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 2));
+
+		// brackets out of reach are matched on their own
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 3]));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+
+		// drop down over the empty bracket, once the distance is widened
+		System::set_block_number(20);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 4]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+	});
+}
+
+#[test]
+fn test_matching_type_same() {
+	new_test_ext().execute_with(|| {
+		// only the admin switches the matching type
+		assert_err!(
+			MatchMaker::set_matching_type(Origin::signed(1), MatchingType::Same),
+			BadOrigin
+		);
+		assert_eq!(MatchMaker::matching_type(), MatchingType::Simple);
+		System::set_block_number(1);
+		assert_ok!(MatchMaker::set_matching_type(Origin::root(), MatchingType::Same));
+		assert_eq!(MatchMaker::matching_type(), MatchingType::Same);
+		System::assert_last_event(Event::MatchMaker(crate::Event::MatchingTypeSet(
+			MatchingType::Same,
+		)));
+
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(5, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(6, 0, 2));
+
+		// only brackets with enough players create a match
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 3]));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![5, 6]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(2));
+
+		assert_ok!(MatchMaker::do_add_queue(7, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 7]));
+		assert_eq!(MatchMaker::do_queue_size(0, 1), Ok(1));
+	});
+}
+
+#[test]
+fn test_matching_type_mix() {
+	new_test_ext().execute_with(|| {
+		MatchingStrategy::<Test>::put(MatchingType::Mix);
+
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 2));
+
+		// at most one player of each bracket, within the widened distance
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+		System::set_block_number(20);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 4]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(2));
+
+		assert_ok!(MatchMaker::do_add_queue(5, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(6, 0, 2));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 5]));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![3, 6]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+	});
+}
+
+#[test]
+fn test_bracket_widening() {
+	new_test_ext().execute_with(|| {
+		MatchingStrategy::<Test>::put(MatchingType::Same);
+
+		System::set_block_number(1);
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 2));
+
+		System::set_block_number(5);
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 1));
+
+		// no widening before the widening period passed
+		System::set_block_number(10);
+		assert!(MatchMaker::do_try_match(0).is_err());
+
+		// longest waiting player reaches the neighbour bracket
+		System::set_block_number(11);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 3]));
+		assert!(MatchMaker::do_try_match(0).is_err());
+
+		// new player in distance two has to wait for the second widening
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 0));
+		System::set_block_number(20);
+		assert!(MatchMaker::do_try_match(0).is_err());
+		System::set_block_number(21);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 4]));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(0));
+	});
+}
+
+#[test]
+fn test_queue_expiry() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 1));
+
+		System::set_block_number(30);
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 0));
+
+		// nobody expired yet
+		MatchMaker::on_initialize(50);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(4));
+
+		// only two players are evicted per block
+		MatchMaker::on_initialize(51);
+		assert_eq!(MatchMaker::do_is_queued(1), false);
+		assert_eq!(MatchMaker::do_is_queued(2), false);
+		assert_eq!(MatchMaker::do_is_queued(3), true);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(2));
+
+		MatchMaker::on_initialize(52);
+		assert_eq!(MatchMaker::do_is_queued(3), false);
+		assert_eq!(MatchMaker::do_is_queued(4), true);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(1));
+
+		MatchMaker::on_initialize(80);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(0));
+	});
+}
+
+#[test]
+fn test_brackets_model() {
+	new_test_ext().execute_with(|| {
+		// start close to the end of the index range, to cover the wrap around
+		BracketIndices::<Test>::insert((0, 0), (BufferIndex::MAX - 5, BufferIndex::MAX - 5));
+
+		let max_queue_size = MaxQueueSize::get() as usize;
+
+		// simple linear congruential generator, to get a reproducible sequence
+		let mut seed: u32 = 42;
+		let mut random = || {
+			seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+			(seed >> 16) as usize
+		};
+
+		let mut model: VecDeque<u64> = VecDeque::new();
+		let mut next_account: u64 = 1;
+		for _i in 0..1_000 {
+			let mut queue = MatchMaker::queue_transient(0);
+
+			match random() % 5 {
+				0 | 1 => {
+					let player = PlayerStruct {
+						account: next_account,
+						queued_at: 0,
+						party_size: 1,
+						attributes: 0,
+					};
+					let result = queue.push(0, next_account, player);
+					if model.len() < max_queue_size {
+						assert_eq!(result, Ok(()));
+						model.push_back(next_account);
+					} else {
+						assert_eq!(result, Err(BracketsError::QueueFull));
+					}
+					next_account += 1;
+				},
+				2 => {
+					assert_eq!(queue.pop(0).map(|p| p.account), model.pop_front());
+				},
+				3 => {
+					let player = PlayerStruct {
+						account: next_account,
+						queued_at: 0,
+						party_size: 1,
+						attributes: 0,
+					};
+					let result = queue.push_front(0, next_account, player);
+					if model.len() < max_queue_size {
+						assert_eq!(result, Ok(()));
+						model.push_front(next_account);
+					} else {
+						assert_eq!(result, Err(BracketsError::QueueFull));
+					}
+					next_account += 1;
+				},
+				_ =>
+					if !model.is_empty() {
+						let account = model.remove(random() % model.len()).unwrap();
+						assert_eq!(queue.remove(0, account).map(|p| p.account), Some(account));
+					},
+			}
+
+			assert_eq!(queue.size(0) as usize, model.len());
+			assert_eq!(queue.is_empty(0), model.is_empty());
+			assert_eq!(queue.peek(0).map(|p| p.account), model.front().cloned());
+		}
+
+		// rejected, popped and removed accounts aren't left behind as queued
+		for account in 1..next_account {
+			assert_eq!(MatchMaker::do_is_queued(account), model.contains(&account));
+		}
+		// reverse index points to the slot of each queued account
+		let (start, _) = MatchMaker::indices((0, 0));
+		for (position, account) in model.iter().enumerate() {
+			let (mode, bracket, index) = MatchMaker::key_index(account).unwrap();
+			assert_eq!((mode, bracket), (0, 0));
+			assert_eq!(MatchMaker::index_key((0, 0), index), *account);
+			assert_eq!(index, start.wrapping_add(position as BufferIndex));
+		}
+		assert_eq!(MatchMaker::do_queue_size(0, 0).unwrap() as usize, model.len());
+	});
+}
+
+#[test]
+fn test_queue_full() {
+	new_test_ext().execute_with(|| {
+		let max_queue_size = MaxQueueSize::get() as u64;
+
+		for account in 0..max_queue_size {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+
+		// full bracket rejects, other brackets still accept
+		assert_err!(MatchMaker::do_add_queue(max_queue_size, 0, 0), Error::<Test>::QueueFull);
+		assert_eq!(MatchMaker::do_is_queued(max_queue_size), false);
+		assert_ok!(MatchMaker::do_add_queue(max_queue_size, 0, 1));
+
+		// oldest player is still queued and matched first
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(max_queue_size as BufferIndex));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![0, 1]));
+	});
+}
+
+#[test]
+fn test_bracket_of() {
+	new_test_ext().execute_with(|| {
+		let player1 = 1;
+		let player2 = 2;
+
+		assert_eq!(MatchMaker::do_bracket_of(player1), None);
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 1));
+		assert_eq!(MatchMaker::do_bracket_of(player1), Some(2));
+		assert_eq!(MatchMaker::do_bracket_of(player2), Some(1));
+
+		assert_ok!(MatchMaker::do_leave_queue(player2));
+		assert_eq!(MatchMaker::do_bracket_of(player2), None);
+		assert_eq!(MatchMaker::key_index(player2), None);
+
+		assert_ok!(MatchMaker::do_all_empty_queue(0));
+		assert_eq!(MatchMaker::do_bracket_of(player1), None);
+		assert_eq!(MatchMaker::key_index(player1), None);
+	});
+}
+
+#[test]
+fn test_errors() {
+	new_test_ext().execute_with(|| {
+		let player1 = 1;
+		let player2 = 2;
+		let brackets_count = MatchMaker::brackets_count();
+
+		// brackets out of bounds are rejected instead of panicking
+		assert_err!(
+			MatchMaker::do_add_queue(player1, 0, brackets_count),
+			Error::<Test>::InvalidBracket
+		);
+		assert_err!(MatchMaker::do_empty_queue(0, brackets_count), Error::<Test>::InvalidBracket);
+		assert_err!(MatchMaker::do_queue_size(0, brackets_count), Error::<Test>::InvalidBracket);
+		assert_eq!(MatchMaker::do_is_queued(player1), false);
+
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueIsEmpty);
+		assert_ok!(MatchMaker::do_add_queue(player1, 0, 0));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+		assert_ok!(MatchMaker::do_add_queue(player2, 0, 1));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+		System::set_block_number(10);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![player1, player2]));
+	});
+}
+
+#[test]
+fn test_game_modes() {
+	new_test_ext().execute_with(|| {
+		let invalid_mode = GameModes::get().len() as u8;
+
+		// game mode 0 matches two players, game mode 1 three players
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(3, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 1, 0));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(2));
+		assert_eq!(MatchMaker::do_all_queue_size(1), Ok(2));
+
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_err!(MatchMaker::do_try_match(1), Error::<Test>::QueueSizeToLow);
+		assert_ok!(MatchMaker::do_add_queue(5, 1, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![3, 4, 5]));
+
+		// an account is queued in one game mode at a time
+		assert_ok!(MatchMaker::do_add_queue(6, 0, 0));
+		assert_err!(MatchMaker::do_add_queue(6, 1, 0), Error::<Test>::AlreadyQueued);
+		assert_ok!(MatchMaker::do_leave_queue(6));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(0));
+		assert_ok!(MatchMaker::do_add_queue(6, 1, 0));
+		assert_eq!(MatchMaker::do_queue_size(1, 0), Ok(1));
+
+		// emptying a game mode leaves the other game modes untouched
+		assert_ok!(MatchMaker::do_add_queue(7, 0, 1));
+		assert_ok!(MatchMaker::do_all_empty_queue(1));
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(1));
+		assert_eq!(MatchMaker::do_all_queue_size(1), Ok(0));
+
+		assert_err!(MatchMaker::do_add_queue(8, invalid_mode, 0), Error::<Test>::InvalidGameMode);
+		assert_err!(MatchMaker::do_try_match(invalid_mode), Error::<Test>::InvalidGameMode);
+		assert_err!(MatchMaker::do_all_empty_queue(invalid_mode), Error::<Test>::InvalidGameMode);
+	});
+}
+
+#[test]
+fn test_party() {
+	new_test_ext().execute_with(|| {
+		let leader = 1;
+
+		assert_ok!(MatchMaker::create_party(Origin::signed(leader)));
+		assert_err!(
+			MatchMaker::create_party(Origin::signed(leader)),
+			Error::<Test>::AlreadyInParty
+		);
+
+		// only invited accounts can join, till the party is full
+		assert_err!(
+			MatchMaker::join_party(Origin::signed(2), leader),
+			Error::<Test>::NoPartyInvite
+		);
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(leader), 2));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(leader), 3));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(leader), 4));
+		assert_err!(
+			MatchMaker::invite_to_party(Origin::signed(2), 5),
+			Error::<Test>::NotPartyLeader
+		);
+		assert_ok!(MatchMaker::join_party(Origin::signed(2), leader));
+		assert_ok!(MatchMaker::join_party(Origin::signed(3), leader));
+		assert_err!(MatchMaker::join_party(Origin::signed(4), leader), Error::<Test>::PartyFull);
+		assert_eq!(MatchMaker::parties(leader).into_inner(), vec![1, 2, 3]);
+
+		// party is queued by its leader, with the average rating of its players
+		Ratings::<Test>::insert(1, 1300);
+		Ratings::<Test>::insert(2, 1500);
+		Ratings::<Test>::insert(3, 1700);
+		assert_err!(MatchMaker::do_add_queue_by_rating(2, 1), Error::<Test>::NotPartyLeader);
+		assert_err!(MatchMaker::do_add_queue_by_rating(leader, 0), Error::<Test>::PartyTooLarge);
+		assert_ok!(MatchMaker::do_add_queue_by_rating(leader, 1));
+		assert_eq!(MatchMaker::do_is_queued(3), true);
+		assert_eq!(MatchMaker::do_bracket_of(3), Some(1));
+		assert_eq!(MatchMaker::do_all_queue_size(1), Ok(1));
+
+		// party can't change while queued
+		assert_err!(MatchMaker::leave_party(Origin::signed(2)), Error::<Test>::PartyQueued);
+		assert_err!(MatchMaker::join_party(Origin::signed(4), leader), Error::<Test>::PartyQueued);
+
+		// a whole party fills a match
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![1, 2, 3]));
+		assert_eq!(MatchMaker::do_is_queued(2), false);
+
+		// any member can take the party out of the queue
+		assert_ok!(MatchMaker::do_add_queue_by_rating(leader, 1));
+		assert_ok!(MatchMaker::do_leave_queue(3));
+		assert_eq!(MatchMaker::do_is_queued(leader), false);
+
+		assert_ok!(MatchMaker::leave_party(Origin::signed(2)));
+		assert_eq!(MatchMaker::parties(leader).into_inner(), vec![1, 3]);
+		assert_eq!(MatchMaker::party_of(2), None);
+
+		// leaving leader disbands the party
+		assert_ok!(MatchMaker::leave_party(Origin::signed(leader)));
+		assert_eq!(MatchMaker::parties(leader).len(), 0);
+		assert_eq!(MatchMaker::party_of(3), None);
+		assert_eq!(MatchMaker::party_invites(leader, 4), None);
+		assert_err!(MatchMaker::leave_party(Origin::signed(3)), Error::<Test>::NotInParty);
+	});
+}
+
+#[test]
+fn test_party_matching() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(MatchMaker::create_party(Origin::signed(1)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(1), 2));
+		assert_ok!(MatchMaker::join_party(Origin::signed(2), 1));
+
+		// game mode 1 matches three players, a party of two fills two slots
+		assert_ok!(MatchMaker::do_add_queue(3, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(1, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 1, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![3, 1, 2]));
+		assert_err!(MatchMaker::do_try_match(1), Error::<Test>::QueueSizeToLow);
+
+		// a party that doesn't fit the free slots is passed over, but stays queued
+		assert_ok!(MatchMaker::do_add_queue(5, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(1, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(6, 1, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![4, 5, 6]));
+		assert_eq!(MatchMaker::do_bracket_of(2), Some(0));
+	});
+}
+
+#[test]
+fn test_party_starvation() {
+	new_test_ext().execute_with(|| {
+		// parties of two can't share a match of three players
+		for leader in (1..=13).step_by(2) {
+			assert_ok!(MatchMaker::create_party(Origin::signed(leader)));
+			assert_ok!(MatchMaker::invite_to_party(Origin::signed(leader), leader + 1));
+			assert_ok!(MatchMaker::join_party(Origin::signed(leader + 1), leader));
+			assert_ok!(MatchMaker::do_add_queue(leader, 1, 0));
+		}
+		assert_ok!(MatchMaker::do_add_queue(15, 1, 0));
+		assert_eq!(MatchMaker::do_queue_size(1, 0), Ok(MaxQueueSize::get()));
+
+		// the single player queued behind them still fills the match
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![1, 2, 15]));
+		assert_err!(MatchMaker::do_try_match(1), Error::<Test>::QueueSizeToLow);
+	});
+}
+
+#[test]
+fn test_match_acceptance() {
+	new_test_ext().execute_with(|| {
+		AcceptPeriod::set(&5);
+
+		// found match waits for its players to accept
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_eq!(MatchMaker::pending_players(1), Some((0, 0, false)));
+		assert_eq!(MatchMaker::queue_position(1), Some((0, 0, 0)));
+		assert_eq!(MatchMaker::estimated_wait(2), Some(0));
+		assert_eq!(MatchMaker::do_is_queued(1), true);
+		assert_err!(MatchMaker::do_add_queue(1, 0, 0), Error::<Test>::AlreadyQueued);
+		assert_err!(MatchMaker::accept_match(Origin::signed(3)), Error::<Test>::NotMatched);
+
+		assert_ok!(MatchMaker::accept_match(Origin::signed(1)));
+		assert_err!(MatchMaker::accept_match(Origin::signed(1)), Error::<Test>::AlreadyAccepted);
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueIsEmpty);
+
+		// match is handed out once all players accepted
+		assert_ok!(MatchMaker::accept_match(Origin::signed(2)));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::do_is_queued(1), false);
+		assert_eq!(MatchMaker::pending_matches(0, 0), None);
+		assert_err!(MatchMaker::accept_match(Origin::signed(1)), Error::<Test>::NotMatched);
+
+		// a match declined after all players accepted isn't handed out anymore
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 0));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_ok!(MatchMaker::accept_match(Origin::signed(3)));
+		assert_ok!(MatchMaker::accept_match(Origin::signed(4)));
+		assert_ok!(MatchMaker::decline_match(Origin::signed(4)));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+		assert_eq!(MatchMaker::do_is_queued(3), true);
+	});
+}
+
+#[test]
+fn test_match_decline() {
+	new_test_ext().execute_with(|| {
+		AcceptPeriod::set(&5);
+
+		for account in 1..=4 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_ok!(MatchMaker::do_add_queue(5, 0, 0));
+		// match 0 of players 1 and 2, match 1 of players 3 and 4, one match per call
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_eq!(MatchMaker::match_count(), 1);
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+		assert_eq!(MatchMaker::match_count(), 2);
+
+		// decliner is penalised and removed, the other player goes back to the front
+		assert_ok!(MatchMaker::accept_match(Origin::signed(1)));
+		assert_ok!(MatchMaker::decline_match(Origin::signed(2)));
+		assert_eq!(MatchMaker::ratings(2), 1500 - DeclinePenalty::get());
+		assert_eq!(MatchMaker::ratings(1), 1500);
+		assert_eq!(MatchMaker::do_is_queued(2), false);
+		assert_eq!(PendingPlayers::<Test>::contains_key(1), false);
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(2));
+
+		// player not accepting in time is penalised and removed as well
+		assert_ok!(MatchMaker::accept_match(Origin::signed(3)));
+		MatchMaker::on_initialize(4);
+		assert_eq!(MatchMaker::pending_players(4), Some((0, 1, false)));
+		MatchMaker::on_initialize(5);
+		assert_eq!(MatchMaker::ratings(4), 1500 - DeclinePenalty::get());
+		assert_eq!(MatchMaker::ratings(3), 1500);
+		assert_eq!(MatchMaker::do_is_queued(4), false);
+
+		// accepted players are matched ahead of the players queued after them
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(3));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_eq!(MatchMaker::pending_players(3), Some((0, 2, false)));
+		assert_eq!(MatchMaker::pending_players(1), Some((0, 2, false)));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(1));
+		assert_eq!(MatchMaker::do_is_queued(5), true);
+
+		// requeued players keep their order ahead of the players queued after them
+		for account in 6..=9 {
+			assert_ok!(MatchMaker::do_add_queue(account, 1, 0));
+		}
+		assert_err!(MatchMaker::do_try_match(1), Error::<Test>::AwaitingAcceptance);
+		assert_ok!(MatchMaker::accept_match(Origin::signed(6)));
+		assert_ok!(MatchMaker::accept_match(Origin::signed(7)));
+		assert_ok!(MatchMaker::decline_match(Origin::signed(8)));
+		assert_eq!(MatchMaker::queue_position(6), Some((1, 0, 0)));
+		assert_eq!(MatchMaker::queue_position(7), Some((1, 0, 1)));
+		assert_eq!(MatchMaker::queue_position(9), Some((1, 0, 2)));
+	});
+}
+
+#[test]
+fn test_queue_introspection() {
+	new_test_ext().execute_with(|| {
+		for account in 1..=3 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 2));
+		assert_eq!(MatchMaker::bracket_sizes(0), vec![3, 0, 1]);
+		assert_eq!(MatchMaker::total_queued(0), 4);
+		assert_eq!(MatchMaker::queue_position(2), Some((0, 0, 1)));
+		assert_eq!(MatchMaker::queue_position(4), Some((0, 2, 0)));
+		assert_eq!(MatchMaker::queue_position(5), None);
+
+		// no estimate without a match
+		assert_eq!(MatchMaker::estimated_wait(3), None);
+
+		System::set_block_number(10);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::queue_position(3), Some((0, 0, 0)));
+		assert_ok!(MatchMaker::do_add_queue(5, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(6, 0, 0));
+		assert_eq!(MatchMaker::estimated_wait(3), Some(10));
+		assert_eq!(MatchMaker::estimated_wait(6), Some(20));
+
+		// average moves towards the latest interval
+		System::set_block_number(14);
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![3, 5]));
+		assert_eq!(MatchMaker::match_throughput(0), (14, 8));
+		assert_eq!(MatchMaker::estimated_wait(6), Some(8));
+
+		// positions count the players of the parties queued ahead
+		assert_ok!(MatchMaker::create_party(Origin::signed(7)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(7), 8));
+		assert_ok!(MatchMaker::join_party(Origin::signed(8), 7));
+		assert_ok!(MatchMaker::do_add_queue(7, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(9, 0, 1));
+		assert_eq!(MatchMaker::queue_position(8), Some((0, 1, 0)));
+		assert_eq!(MatchMaker::queue_position(9), Some((0, 1, 2)));
+		assert_eq!(MatchMaker::estimated_wait(9), Some(16));
+	});
+}
+
+#[test]
+fn test_recent_opponents() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::recent_opponents(1).into_inner(), vec![(2, 1)]);
+		assert_eq!(MatchMaker::recent_opponents(2).into_inner(), vec![(1, 1)]);
+
+		// recent opponents queued together are matched with someone else
+		for account in 1..=3 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 3]));
+
+		// without anyone else they are matched again
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 1]));
+		assert_eq!(MatchMaker::recent_opponents(1).into_inner(), vec![(3, 1), (2, 1)]);
+
+		// opponents are forgotten once the rematch window passed
+		System::set_block_number(21);
+		for account in 1..=3 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::recent_opponents(1).into_inner(), vec![(2, 21)]);
+
+		// only the latest opponents are remembered
+		assert_ok!(MatchMaker::do_leave_queue(3));
+		assert_ok!(MatchMaker::do_add_queue(1, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(5, 1, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![1, 4, 5]));
+		assert_ok!(MatchMaker::do_add_queue(1, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(6, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(7, 1, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![1, 6, 7]));
+		assert_eq!(
+			MatchMaker::recent_opponents(1).into_inner(),
+			vec![(4, 21), (5, 21), (6, 21), (7, 21)]
+		);
+	});
+}
+
+#[test]
+fn test_match_filter() {
+	new_test_ext().execute_with(|| {
+		assert_err!(MatchMaker::set_attributes(Origin::signed(2), 2, 1), BadOrigin);
+		assert_ok!(MatchMaker::set_attributes(Origin::root(), 2, 1));
+		assert_ok!(MatchMaker::set_attributes(Origin::root(), 4, 1));
+		assert_eq!(MatchMaker::attributes(2), 1);
+
+		// only players of the same region are matched together
+		for account in 1..=4 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 3]));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![2, 4]));
+
+		// the filter applies across brackets, and isn't dropped without an alternative
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::QueueSizeToLow);
+
+		// attributes are taken along when queuing
+		System::set_block_number(10);
+		assert_ok!(MatchMaker::do_leave_queue(2));
+		assert_ok!(MatchMaker::set_attributes(Origin::root(), 2, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+
+		// the filter applies to every player of a party
+		assert_ok!(MatchMaker::create_party(Origin::signed(5)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(5), 6));
+		assert_ok!(MatchMaker::join_party(Origin::signed(6), 5));
+		assert_ok!(MatchMaker::set_attributes(Origin::root(), 6, 1));
+		assert_ok!(MatchMaker::do_add_queue(5, 1, 0));
+		assert_ok!(MatchMaker::do_add_queue(7, 1, 0));
+		assert_err!(MatchMaker::do_try_match(1), Error::<Test>::QueueSizeToLow);
+		assert_ok!(MatchMaker::set_attributes(Origin::root(), 6, 0));
+		assert_eq!(MatchMaker::do_try_match(1), Ok(vec![5, 6, 7]));
+	});
+}
+
+#[test]
+fn test_hook_matching() {
+	new_test_ext().execute_with(|| {
+		// no matching without a budget
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		MatchMaker::on_initialize(1);
+		assert_eq!(matches(), vec![]);
+
+		MaxMatchesPerBlock::set(&2);
+		for account in 3..=6 {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		for account in 7..=9 {
+			assert_ok!(MatchMaker::do_add_queue(account, 1, 0));
+		}
+
+		// game modes take turns to go first, until the budget is used up
+		MatchMaker::on_initialize(1);
+		assert_eq!(matches(), vec![(1, vec![7, 8, 9]), (0, vec![1, 2])]);
+		MatchMaker::on_initialize(2);
+		assert_eq!(matches()[2..], [(0, vec![3, 4]), (0, vec![5, 6])]);
+		MatchMaker::on_initialize(3);
+		assert_eq!(matches().len(), 4);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(0));
+
+		// players stay queued while the handler has no capacity for their match
+		HandlerFull::set(&true);
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		MatchMaker::on_initialize(4);
+		assert_eq!(matches().len(), 4);
+		assert_eq!(MatchMaker::do_all_queue_size(0), Ok(2));
+		HandlerFull::set(&false);
+		MatchMaker::on_initialize(5);
+		assert_eq!(matches()[4..], [(0, vec![1, 2])]);
+
+		// players of a match no handler takes leave the queue with their deposit
+		QueueDeposit::set(&10);
+		for account in 10..=13 {
+			Balances::make_free_balance_be(&account, 100);
+			assert_ok!(MatchMaker::do_add_queue(account, 2, 0));
+		}
+		assert_eq!(Balances::reserved_balance(10), 10);
+		MatchMaker::on_initialize(6);
+		assert_eq!(matches().len(), 5);
+		assert_eq!(MatchMaker::do_is_queued(10), false);
+		assert_eq!(Balances::reserved_balance(10), 0);
+		assert_eq!(MatchMaker::deposits(13), None);
+	});
+}
+
+#[test]
+fn test_fill_timeout() {
+	new_test_ext().execute_with(|| {
+		// game mode 2 matches four players, or at least two after the fill timeout
+		System::set_block_number(1);
+		for account in 1..=3 {
+			assert_ok!(MatchMaker::do_add_queue(account, 2, 0));
+		}
+		assert_err!(MatchMaker::do_try_match(2), Error::<Test>::QueueSizeToLow);
+		System::set_block_number(10);
+		assert_err!(MatchMaker::do_try_match(2), Error::<Test>::QueueSizeToLow);
+
+		System::set_block_number(11);
+		assert_eq!(MatchMaker::do_try_match(2), Ok(vec![1, 2, 3]));
+
+		// full matches don't wait for the timeout
+		for account in 4..=8 {
+			assert_ok!(MatchMaker::do_add_queue(account, 2, 0));
+		}
+		assert_eq!(MatchMaker::do_try_match(2), Ok(vec![4, 5, 6, 7]));
+
+		// the minimum of players is needed after the timeout as well
+		System::set_block_number(30);
+		assert_err!(MatchMaker::do_try_match(2), Error::<Test>::QueueSizeToLow);
+		assert_eq!(MatchMaker::do_all_queue_size(2), Ok(1));
+
+		// only a timeout of one of its own players starts a match with less players
+		assert_ok!(MatchMaker::do_leave_queue(8));
+		assert_ok!(MatchMaker::do_add_queue(1, 2, 0));
+		System::set_block_number(35);
+		assert_ok!(MatchMaker::do_add_queue(2, 2, 2));
+		assert_ok!(MatchMaker::do_add_queue(3, 2, 2));
+		System::set_block_number(40);
+		assert_err!(MatchMaker::do_try_match(2), Error::<Test>::QueueSizeToLow);
+		System::set_block_number(45);
+		assert_eq!(MatchMaker::do_try_match(2), Ok(vec![2, 3]));
+	});
+}
+
+#[test]
+fn test_queue_deposit() {
+	new_test_ext().execute_with(|| {
+		QueueDeposit::set(&10);
+		for account in 1..=4 {
+			Balances::make_free_balance_be(&account, 100);
+		}
+		Balances::make_free_balance_be(&5, 5);
+
+		// deposit is reserved while queued
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_eq!(Balances::reserved_balance(1), 10);
+		assert_eq!(MatchMaker::deposits(1), Some(10));
+		assert_ok!(MatchMaker::do_leave_queue(1));
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(MatchMaker::deposits(1), None);
+
+		// every player of a party has to afford the deposit
+		assert_err!(MatchMaker::do_add_queue(5, 0, 0), Error::<Test>::InsufficientDeposit);
+		assert_eq!(MatchMaker::do_is_queued(5), false);
+		assert_ok!(MatchMaker::create_party(Origin::signed(1)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(1), 5));
+		assert_ok!(MatchMaker::join_party(Origin::signed(5), 1));
+		assert_err!(MatchMaker::do_add_queue(1, 0, 0), Error::<Test>::InsufficientDeposit);
+		assert_eq!(MatchMaker::do_is_queued(1), false);
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_ok!(MatchMaker::leave_party(Origin::signed(5)));
+
+		// deposit is held until the game pallet reports the match completed or a no-show
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 0));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(Balances::reserved_balance(1), 10);
+		assert_err!(MatchMaker::do_add_queue(1, 0, 0), Error::<Test>::DepositHeld);
+		assert_eq!(Balances::reserved_balance(1), 10);
+		MatchMaker::release_deposit(1);
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 100);
+		MatchMaker::slash_deposit(2);
+		assert_eq!(Balances::reserved_balance(2), 0);
+		assert_eq!(Balances::free_balance(2), 90);
+
+		// decliners are slashed, the other players stay queued with their deposit
+		AcceptPeriod::set(&5);
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 0));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_ok!(MatchMaker::decline_match(Origin::signed(3)));
+		assert_eq!(Balances::free_balance(3), 90);
+		assert_eq!(Balances::reserved_balance(3), 0);
+		assert_eq!(Balances::reserved_balance(4), 10);
+		assert_eq!(MatchMaker::do_is_queued(4), true);
+
+		// emptying the game mode drops its pending matches without a penalty
+		Balances::make_free_balance_be(&6, 100);
+		assert_ok!(MatchMaker::do_add_queue(6, 0, 0));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_ok!(MatchMaker::accept_match(Origin::signed(4)));
+		assert_ok!(MatchMaker::do_all_empty_queue(0));
+		assert_eq!(MatchMaker::do_is_queued(4), false);
+		assert_eq!(PendingPlayers::<Test>::contains_key(6), false);
+		assert_eq!(Balances::free_balance(4), 100);
+		assert_eq!(Balances::free_balance(6), 100);
+		assert_err!(MatchMaker::accept_match(Origin::signed(6)), Error::<Test>::NotMatched);
+	});
+}
+
+#[test]
+fn test_set_brackets_count() {
+	new_test_ext().execute_with(|| {
+		assert_err!(MatchMaker::set_brackets_count(Origin::signed(1), 1), BadOrigin);
+		assert_err!(
+			MatchMaker::set_brackets_count(Origin::root(), 0),
+			Error::<Test>::InvalidBracketsCount
+		);
+
+		// players of removed brackets move into the new top bracket
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 0));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 1));
+		assert_ok!(MatchMaker::set_brackets_count(Origin::root(), 2));
+		assert_eq!(MatchMaker::brackets_count(), 2);
+		assert_eq!(MatchMaker::do_queue_size(0, 1), Ok(3));
+		assert_eq!(MatchMaker::do_bracket_of(1), Some(1));
+		assert_eq!(BracketIndices::<Test>::contains_key((0, 2)), false);
+		assert_err!(MatchMaker::do_add_queue(5, 0, 2), Error::<Test>::InvalidBracket);
+
+		// players that don't fit into the new top bracket anymore are removed
+		let max_queue_size: u16 = <Test as crate::Config>::MaxQueueSize::get();
+		for account in 10..(9 + max_queue_size as u64) {
+			assert_ok!(MatchMaker::do_add_queue(account, 0, 0));
+		}
+		assert_ok!(MatchMaker::set_brackets_count(Origin::root(), 1));
+		assert_eq!(MatchMaker::do_queue_size(0, 0), Ok(max_queue_size));
+		assert_eq!(MatchMaker::do_is_queued(1), false);
+		assert_eq!(MatchMaker::do_is_queued(2), false);
+		assert_eq!(MatchMaker::do_is_queued(3), true);
+
+		// added brackets start out empty
+		assert_ok!(MatchMaker::set_brackets_count(Origin::root(), 3));
+		assert_eq!(MatchMaker::do_queue_size(0, 2), Ok(0));
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 2));
+		assert_eq!(MatchMaker::do_bracket_of(1), Some(2));
+
+		// moved players are merged in by the block they queued at
+		System::set_block_number(1);
+		assert_ok!(MatchMaker::do_add_queue(5, 0, 1));
+		assert_ok!(MatchMaker::set_brackets_count(Origin::root(), 2));
+		assert_eq!(MatchMaker::queue_position(1), Some((0, 1, 0)));
+		assert_eq!(MatchMaker::queue_position(5), Some((0, 1, 1)));
+	});
+}
+
+#[test]
+fn test_brackets_migration() {
+	new_test_ext().execute_with(|| {
+		// a chain that queued players in five brackets before the runtime reduced them to three
+		BracketsCount::<Test>::put(5);
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 4));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		BracketsCount::<Test>::kill();
+		StorageVersion::new(0).put::<MatchMaker>();
+
+		migrations::v1::migrate::<Test, ()>();
+
+		assert_eq!(MatchMaker::on_chain_storage_version(), 1);
+		assert_eq!(MatchMaker::brackets_count(), 3);
+		assert_eq!(MatchMaker::do_bracket_of(1), Some(2));
+		assert_eq!(MatchMaker::do_bracket_of(2), Some(1));
+		assert_eq!(BracketIndices::<Test>::contains_key((0, 4)), false);
+		assert_ok!(MatchMaker::do_leave_queue(1));
+	});
+}
+
+#[test]
+fn test_queue_migration() {
+	new_test_ext().execute_with(|| {
+		// a queue of players before version 1, keyed by bracket only
+		let pallet = b"MatchMaker";
+		let bracket_key = Blake2_128Concat::hash(&[1]);
+		let key = |second: Vec<u8>| [bracket_key.clone(), Blake2_128Concat::hash(&second)].concat();
+		put_storage_value(pallet, b"BracketIndices", &bracket_key, (0 as BufferIndex, 2));
+		for (index, account) in [(0 as BufferIndex, 1u64), (1, 2)] {
+			put_storage_value(pallet, b"BracketIndexKeyMap", &key(index.encode()), account);
+			// players only stored their account
+			put_storage_value(pallet, b"BracketKeyValueMap", &key(account.encode()), account);
+		}
+		StorageVersion::new(0).put::<MatchMaker>();
+
+		System::set_block_number(7);
+		migrations::v1::migrate::<Test, ()>();
+
+		// the queue moved into game mode 0
+		assert_eq!(MatchMaker::indices((0, 1)), (0, 2));
+		assert_eq!(MatchMaker::index_key((0, 1), 1), 2);
+		assert_eq!(
+			get_storage_value::<(BufferIndex, BufferIndex)>(
+				pallet,
+				b"BracketIndices",
+				&bracket_key
+			),
+			None
+		);
+		// the wait of its players is counted from the migration on
+		assert_eq!(
+			MatchMaker::key_value((0, 1), 2),
+			PlayerStruct { account: 2, queued_at: 7, party_size: 1, attributes: 0 }
+		);
+		// and they are indexed by account
+		assert_eq!(MatchMaker::key_index(1), Some((0, 1, 0)));
+		assert_eq!(MatchMaker::key_index(2), Some((0, 1, 1)));
+		assert_eq!(MatchMaker::do_bracket_of(2), Some(1));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert_eq!(MatchMaker::do_is_queued(1), false);
+	});
+}
+
+#[test]
+fn test_storage_deque() {
+	new_test_ext().execute_with(|| {
+		type Deque = StorageDeque<(u8, u8), u64, BracketIndices<Test>, BracketIndexKeyMap<Test>>;
+
+		// indices wrap around at both ends
+		BracketIndices::<Test>::insert((9, 0), (BufferIndex::MAX - 1, BufferIndex::MAX - 1));
+		{
+			let mut deque = Deque::new((9, 0), 4);
+			assert_eq!(deque.is_empty(), true);
+			assert_eq!(deque.pop_front(), None);
+			assert_eq!(deque.pop_back(), None);
+			assert_eq!(deque.peek_front(), None);
+
+			assert_eq!(deque.push_back(1), Ok(BufferIndex::MAX - 1));
+			assert_eq!(deque.push_back(2), Ok(BufferIndex::MAX));
+			assert_eq!(deque.push_back(3), Ok(0));
+			assert_eq!(deque.push_front(4), Ok(BufferIndex::MAX - 2));
+			assert_eq!(deque.push_back(5), Err(DequeError::Full));
+			assert_eq!(deque.push_front(5), Err(DequeError::Full));
+			assert_eq!(deque.is_full(), true);
+			assert_eq!(deque.front(10), vec![4, 1, 2, 3]);
+			assert_eq!(deque.peek_front(), Some(4));
+			assert_eq!(deque.peek_back(), Some(3));
+		}
+
+		// changes are committed on drop
+		assert_eq!(MatchMaker::indices((9, 0)), (BufferIndex::MAX - 2, 1));
+		{
+			let mut deque = Deque::new((9, 0), 4);
+			assert_eq!(deque.len(), 4);
+			assert_eq!(deque.contains(&2), true);
+
+			// removing keeps the order and reports the shifted items
+			let mut shifted = Vec::new();
+			let index = deque.position(&1).unwrap();
+			assert_eq!(deque.remove(index, |item, index| shifted.push((*item, index))), Some(1));
+			assert_eq!(shifted, vec![(2, BufferIndex::MAX - 1), (3, BufferIndex::MAX)]);
+			assert_eq!(deque.remove(index.wrapping_add(2), |_, _| {}), None);
+			assert_eq!(deque.remove_item(&5), None);
+			assert_eq!(deque.front(10), vec![4, 2, 3]);
+
+			assert_eq!(deque.pop_back(), Some(3));
+			assert_eq!(deque.pop_front(), Some(4));
+			assert_eq!(deque.remove_item(&2), Some(2));
+			assert_eq!(deque.is_empty(), true);
+		}
+		assert_eq!(MatchMaker::indices((9, 0)), (BufferIndex::MAX - 1, BufferIndex::MAX - 1));
+		assert_eq!(BracketIndexKeyMap::<Test>::iter_prefix((9, 0)).count(), 0);
+	});
+}
+
+#[test]
+fn test_match_events() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let events = || -> Vec<crate::Event<Test>> {
+			System::events()
+				.into_iter()
+				.filter_map(|record| match record.event {
+					Event::MatchMaker(event) => Some(event),
+					_ => None,
+				})
+				.collect()
+		};
+
+		// queued players are reported with their bracket and position
+		assert_ok!(MatchMaker::do_add_queue(1, 0, 1));
+		assert_ok!(MatchMaker::do_add_queue(2, 0, 1));
+		assert!(matches!(events()[0], crate::Event::Queued(ref p, 1, 0) if p.account == 1));
+		assert!(matches!(events()[1], crate::Event::Queued(ref p, 1, 1) if p.account == 2));
+
+		// a formed match is reported once, with the bracket of each player
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![1, 2]));
+		assert!(matches!(events()[2], crate::Event::Popped(ref p, 1) if p.account == 1));
+		assert!(matches!(events()[3], crate::Event::Popped(ref p, 1) if p.account == 2));
+		assert_eq!(
+			events()[4],
+			crate::Event::Matched { players: vec![1, 2], brackets: vec![1, 1], match_id: 0 }
+		);
+
+		// an accepted match keeps the id it was found with
+		AcceptPeriod::set(&5);
+		assert_ok!(MatchMaker::do_add_queue(3, 0, 2));
+		assert_ok!(MatchMaker::do_add_queue(4, 0, 2));
+		assert_err!(MatchMaker::do_try_match(0), Error::<Test>::AwaitingAcceptance);
+		assert_eq!(events().last(), Some(&crate::Event::MatchFound(1, vec![3, 4])));
+		assert_ok!(MatchMaker::accept_match(Origin::signed(3)));
+		assert_ok!(MatchMaker::accept_match(Origin::signed(4)));
+		assert_eq!(MatchMaker::do_try_match(0), Ok(vec![3, 4]));
+		assert_eq!(
+			events().last(),
+			Some(&crate::Event::Matched { players: vec![3, 4], brackets: vec![2, 2], match_id: 1 })
+		);
+		assert_eq!(MatchMaker::match_count(), 2);
 	});
 }
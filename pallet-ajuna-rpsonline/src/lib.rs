@@ -4,8 +4,14 @@
 /// Learn more about FRAME and the core library of Substrate FRAME pallets:
 /// <https://substrate.dev/docs/en/knowledgebase/runtime/frame>
 use codec::{Decode, Encode};
-use frame_support::traits::{schedule::Named, LockIdentifier, Randomness};
-use pallet_matchmaker::MatchFunc;
+use frame_support::{
+	traits::{
+		schedule::{DispatchTime, Named},
+		Get, LockIdentifier, Randomness,
+	},
+	weights::Weight,
+};
+use pallet_matchmaker::{GameModeId, MatchFunc, MatchId, MatchResult, OnMatched};
 use sp_io::hashing::blake2_256;
 use sp_runtime::traits::{Dispatchable, Hash, TrailingZeroInput};
 use sp_std::vec::Vec;
@@ -51,6 +57,8 @@ pub enum GameState<AccountId> {
 	Prepare(Vec<AccountId>),
 	Running(AccountId),
 	Finished(AccountId),
+	// finished without a winner, no player acted in time
+	Draw,
 }
 impl<AccountId> Default for GameState<AccountId> {
 	fn default() -> Self {
@@ -107,7 +115,6 @@ impl<Hash, AccountId, BlockNumber> Game<Hash, AccountId, BlockNumber> {
 
 const PLAYER_1: u8 = 1;
 const PLAYER_2: u8 = 2;
-const MAX_GAMES_PER_BLOCK: u8 = 10;
 const MAX_BLOCKS_PER_TURN: u8 = 10;
 const CLEANUP_BOARDS_AFTER: u8 = 20;
 
@@ -135,6 +142,10 @@ pub mod pallet {
 		type PalletsOrigin: From<frame_system::RawOrigin<Self::AccountId>>;
 
 		type MatchMaker: MatchFunc<Self::AccountId>;
+
+		/// Constant game mode of the matchmaker, players of this game are queued in.
+		#[pallet::constant]
+		type GameMode: Get<GameModeId>;
 	}
 
 	#[pallet::pallet]
@@ -173,6 +184,17 @@ pub mod pallet {
 	/// Store players active games, currently only one game per player allowed.
 	pub type PlayerGame<T: Config> = StorageMap<_, Identity, T::AccountId, T::Hash, ValueQuery>;
 
+	#[pallet::storage]
+	#[pallet::getter(fn game_schedules)]
+	/// Store games open schedules.
+	pub type GameSchedules<T: Config> =
+		StorageMap<_, Identity, T::Hash, Option<Vec<u8>>, ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn game_match)]
+	/// Store the match of the games the matchmaker created, only their results are rated.
+	pub type GameMatches<T: Config> = StorageMap<_, Identity, T::Hash, MatchId, OptionQuery>;
+
 	// The genesis config type.
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
@@ -232,42 +254,12 @@ pub mod pallet {
 		BadSetup,
 		/// Bad reveal, trying to cheat?
 		BadReveal,
-		/// Player is already queued.
-		AlreadyQueued,
 		/// Wrong phase state for action.
 		WrongPhaseState,
 	}
 
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
-		// `on_initialize` is executed at the beginning of the block before any extrinsic are
-		// dispatched.
-		//
-		// This function must return the weight consumed by `on_initialize` and `on_finalize`.
-		fn on_initialize(_: T::BlockNumber) -> Weight {
-			// Anything that needs to be done at the start of the block.
-			// We don't do anything here.
-
-			// initial weights
-			let mut tot_weights = 10_000;
-			for _i in 0..MAX_GAMES_PER_BLOCK {
-				// try to create a match till we reached max games or no more matches available
-				let result = T::MatchMaker::try_match();
-				// if result is not empty we have a valid match
-				if !result.is_empty() {
-					// Create new game
-					let _game_id = Self::create_game(result);
-					// weights need to be adjusted
-					tot_weights = tot_weights + T::DbWeight::get().reads_writes(1, 1);
-					continue
-				}
-				break
-			}
-
-			// return standard weigth for trying to fiond a match
-			return tot_weights
-		}
-
 		// `on_finalize` is executed at the end of block after all extrinsic are dispatched.
 		fn on_finalize(_n: BlockNumberFor<T>) {
 			// Perform necessary data/state clean up here.
@@ -336,8 +328,8 @@ pub mod pallet {
 			ensure!(sender != opponent, Error::<T>::NoFakePlay);
 
 			// Don't allow queued player to create a game.
-			ensure!(!T::MatchMaker::is_queued(sender.clone()), Error::<T>::AlreadyQueued);
-			ensure!(!T::MatchMaker::is_queued(opponent.clone()), Error::<T>::AlreadyQueued);
+			T::MatchMaker::ensure_not_queued(sender.clone())?;
+			T::MatchMaker::ensure_not_queued(opponent.clone())?;
 
 			// Make sure players have no board open.
 			ensure!(!PlayerGame::<T>::contains_key(&sender), Error::<T>::PlayerHasGame);
@@ -347,8 +339,8 @@ pub mod pallet {
 			players.push(sender.clone());
 			players.push(opponent.clone());
 
-			// Create new game
-			let _game_id = Self::create_game(players);
+			// Create new game, games the players didn't queue for aren't rated
+			let _game_id = Self::create_game(players, None);
 
 			Ok(())
 		}
@@ -358,15 +350,25 @@ pub mod pallet {
 		pub fn queue(origin: OriginFor<T>) -> DispatchResult {
 			let sender = ensure_signed(origin)?;
 
-			// Make sure player has no board open.
-			ensure!(!PlayerGame::<T>::contains_key(&sender), Error::<T>::PlayerHasGame);
-
-			let bracket: u8 = 0;
-			// Add player to queue, duplicate check is done in matchmaker.
-			if !T::MatchMaker::add_queue(sender, bracket) {
-				return Err(Error::<T>::AlreadyQueued)?
+			// Make sure no player of the party has a game open.
+			for member in T::MatchMaker::party_members(sender.clone()) {
+				ensure!(!PlayerGame::<T>::contains_key(&member), Error::<T>::PlayerHasGame);
 			}
 
+			// Add player to the queue of its rating bracket, errors are reported by matchmaker.
+			T::MatchMaker::add_queue_by_rating(sender, T::GameMode::get())?;
+
+			Ok(())
+		}
+
+		/// Remove sender from the queue, before being matched into a game.
+		#[pallet::weight(10_000 + T::MatchMaker::leave_queue_weight())]
+		pub fn leave_queue(origin: OriginFor<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			// Remove player from queue, errors are reported by matchmaker.
+			T::MatchMaker::leave_queue(sender)?;
+
 			Ok(())
 		}
 
@@ -379,7 +381,7 @@ pub mod pallet {
 			ensure!(sender == Self::founder_key().unwrap(), Error::<T>::OnlyFounderAllowed);
 
 			// Empty queues
-			T::MatchMaker::all_empty_queue();
+			T::MatchMaker::all_empty_queue(T::GameMode::get())?;
 
 			Ok(())
 		}
@@ -659,6 +661,77 @@ pub mod pallet {
 
 			Ok(())
 		}
+
+		/// Force the end of a game after max blocks per turn passed without an action.
+		///
+		/// The players the game waits for lose it along with their deposit, a finished game
+		/// is cleaned up instead.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,4))]
+		pub fn force_timeout(
+			origin: OriginFor<T>,
+			game_id: T::Hash,
+			last_action: T::BlockNumber,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+
+			// Make sure game exists.
+			ensure!(Games::<T>::contains_key(&game_id), Error::<T>::GameDoesntExist);
+			let mut game = Self::games(&game_id);
+
+			ensure!(game.last_action == last_action, "There has been an action in between.");
+
+			if let GameState::Finished(_) | GameState::Draw = game.game_state {
+				// do cleanup after the game timed out or finished.
+				<Games<T>>::remove(game_id);
+				<GameMatches<T>>::remove(game_id);
+				for player in game.players.iter() {
+					<PlayerGame<T>>::remove(player);
+				}
+				<GameSchedules<T>>::remove(game_id);
+				return Ok(())
+			}
+
+			// the players the game waits for didn't act in time, a draw if it waits for all
+			let idle = Self::idle_players(&game);
+			let winner = game.players.iter().find(|p| !idle.contains(p)).cloned();
+
+			// Report finished game to update the players ratings, the deposits of the idle
+			// players are slashed.
+			Self::report_result(
+				&game_id,
+				game.players[0].clone(),
+				game.players[1].clone(),
+				winner.as_ref(),
+			);
+			for player in game.players.iter() {
+				if idle.contains(player) {
+					T::MatchMaker::slash_deposit(player.clone());
+				} else {
+					T::MatchMaker::release_deposit(player.clone());
+				}
+			}
+			game.game_state = match winner {
+				Some(winner) => GameState::Finished(winner),
+				None => GameState::Draw,
+			};
+
+			// get current blocknumber
+			let last_action = <frame_system::Pallet<T>>::block_number();
+			game.last_action = last_action;
+
+			// Write next game state back into the storage
+			<Games<T>>::insert(game_id, game);
+
+			// Execute cleanup task
+			let schedule_id = Self::schedule_timeout(
+				game_id,
+				last_action,
+				last_action + CLEANUP_BOARDS_AFTER.into(),
+			);
+			<GameSchedules<T>>::insert(game_id, schedule_id);
+
+			Ok(())
+		}
 	}
 }
 
@@ -687,7 +760,7 @@ impl<T: Config> Pallet<T> {
 		choice_hashed.using_encoded(T::Hashing::hash)
 	}
 
-	fn create_game(players: Vec<T::AccountId>) -> T::Hash {
+	fn create_game(players: Vec<T::AccountId>, match_id: Option<MatchId>) -> T::Hash {
 		// get a random hash as board id
 		let game_id = Self::generate_random_hash(b"create", players[0].clone());
 
@@ -704,12 +777,18 @@ impl<T: Config> Pallet<T> {
 
 		// insert the new game into the storage
 		<Games<T>>::insert(game_id, game);
+		if let Some(match_id) = match_id {
+			<GameMatches<T>>::insert(game_id, match_id);
+		}
 
 		// insert conenction for each player with the game
 		for player in &players {
 			<PlayerGame<T>>::insert(player, game_id);
 		}
 
+		// players have max blocks per turn for each action
+		Self::reschedule_timeout(game_id, block_number);
+
 		// emit event for a new game creation
 		Self::deposit_event(Event::NewGame(game_id));
 
@@ -951,7 +1030,7 @@ impl<T: Config> Pallet<T> {
 				}
 			},
 
-			GameState::Finished(_) => {},
+			GameState::Finished(_) | GameState::Draw => {},
 
 			_ => return false,
 		}
@@ -961,11 +1040,87 @@ impl<T: Config> Pallet<T> {
 		game.last_action = block_number;
 
 		// persist game
-		<Games<T>>::insert(game.id, game);
+		let game_id = game.id;
+		<Games<T>>::insert(game_id, game);
+
+		// the next action has to follow within max blocks per turn
+		Self::reschedule_timeout(game_id, block_number);
 
 		true
 	}
 
+	/// Return the players a game waits for to act.
+	fn idle_players(game: &Game<T::Hash, T::AccountId, T::BlockNumber>) -> Vec<T::AccountId> {
+		match (&game.game_state, &game.phase_state) {
+			(GameState::Initiate(players), _) | (GameState::Prepare(players), _) => players.clone(),
+			(GameState::Running(_), PhaseState::Choose(players)) |
+			(GameState::Running(_), PhaseState::Reveal(players)) => players.clone(),
+			(GameState::Running(player_at_turn), _) => sp_std::vec![player_at_turn.clone()],
+			_ => Vec::new(),
+		}
+	}
+
+	/// Replace the scheduled timeout of a game, after one of its players acted.
+	fn reschedule_timeout(game_id: T::Hash, last_action: T::BlockNumber) {
+		// Cancel scheduled task
+		if let Some(old_schedule_id) = Self::game_schedules(&game_id) {
+			if T::Scheduler::cancel_named(old_schedule_id).is_err() {
+				frame_support::print("LOGIC ERROR: reschedule_timeout/cancel_named failed");
+			}
+		}
+
+		let schedule_id =
+			Self::schedule_timeout(game_id, last_action, last_action + MAX_BLOCKS_PER_TURN.into());
+		<GameSchedules<T>>::insert(game_id, schedule_id);
+	}
+
+	/// Schedule the timeout of a game
+	fn schedule_timeout(
+		game_id: T::Hash,
+		last_action: T::BlockNumber,
+		timeout: T::BlockNumber,
+	) -> Option<Vec<u8>> {
+		let schedule_task_id = (RPSONLINE_ID, game_id, last_action).encode();
+
+		if T::Scheduler::schedule_named(
+			schedule_task_id.clone(),
+			DispatchTime::At(timeout),
+			None,
+			63,
+			frame_system::RawOrigin::Root.into(),
+			Call::force_timeout { game_id, last_action }.into(),
+		)
+		.is_err()
+		{
+			frame_support::print("LOGIC ERROR: schedule_timeout/schedule_named failed");
+			return None
+		}
+
+		Some(schedule_task_id)
+	}
+
+	/// Report the result of a finished game to the matchmaker, no winner is a draw.
+	///
+	/// Only games of matchmaker matches are rated, players can't pick their opponents.
+	fn report_result(
+		game_id: &T::Hash,
+		player: T::AccountId,
+		opponent: T::AccountId,
+		winner: Option<&T::AccountId>,
+	) {
+		if !<GameMatches<T>>::contains_key(game_id) {
+			return
+		}
+
+		let result = match winner {
+			Some(winner) if *winner == player => MatchResult::Won,
+			Some(winner) if *winner == opponent => MatchResult::Lost,
+			_ => MatchResult::Draw,
+		};
+
+		T::MatchMaker::report_result(player, opponent, result);
+	}
+
 	fn get_ninja_index(position_value: u8, player_index: usize) -> u8 {
 		position_value - (player_index as u8 * 16)
 	}
@@ -974,3 +1129,24 @@ impl<T: Config> Pallet<T> {
 		position_value / 16 == player_index as u8
 	}
 }
+
+impl<T: Config> OnMatched<T::AccountId> for Pallet<T> {
+	fn on_matched(
+		mode: GameModeId,
+		match_id: MatchId,
+		players: Vec<T::AccountId>,
+	) -> (bool, Weight) {
+		// matches of other game modes are played elsewhere, a game is played by two
+		if mode != T::GameMode::get() || players.len() != 2 {
+			return (false, 0)
+		}
+		// a player has one game at a time, an open game is never overwritten
+		if players.iter().any(|player| PlayerGame::<T>::contains_key(player)) {
+			return (false, T::DbWeight::get().reads(2))
+		}
+
+		let _game_id = Self::create_game(players, Some(match_id));
+		// the game is scheduled to time out, weights need to be adjusted
+		(true, T::DbWeight::get().reads_writes(6, 8))
+	}
+}
@@ -274,3 +274,148 @@ fn try_simple_rps_game() {
 		assert_eq!(game.last_action, current_block);
 	});
 }
+
+#[test]
+fn test_idle_player_times_out() {
+	new_test_ext().execute_with(|| {
+		let player_1: u64 = 1;
+		let player_2: u64 = 2;
+
+		QueueDeposit::set(&10);
+		Balances::make_free_balance_be(&player_1, 100);
+		Balances::make_free_balance_be(&player_2, 100);
+
+		run_to_block(100);
+
+		assert_ok!(RPSOnline::queue(Origin::signed(player_1)));
+		assert_ok!(RPSOnline::queue(Origin::signed(player_2)));
+		run_next_block();
+		let game_id = RPSOnline::player_game(player_1);
+		assert!(RPSOnline::game_match(game_id).is_some());
+
+		// player 2 doesn't initiate in time, loses the game and its deposit
+		assert_ok!(RPSOnline::initiate(Origin::signed(player_1)));
+		run_to_block(101 + MAX_BLOCKS_PER_TURN as u64);
+		let game = RPSOnline::games(game_id);
+		assert_eq!(game.game_state, GameState::Finished(player_1));
+		assert!(MatchMaker::ratings(player_1) > 1500);
+		assert_eq!(Balances::reserved_balance(player_1), 0);
+		assert_eq!(Balances::free_balance(player_1), 100);
+		assert_eq!(Balances::reserved_balance(player_2), 0);
+		assert_eq!(Balances::free_balance(player_2), 90);
+
+		// finished game is cleaned up, its players are free to queue again
+		run_to_block(101 + (MAX_BLOCKS_PER_TURN + CLEANUP_BOARDS_AFTER) as u64);
+		assert!(!Games::<Test>::contains_key(game_id));
+		assert!(!PlayerGame::<Test>::contains_key(player_2));
+		assert_ok!(RPSOnline::queue(Origin::signed(player_2)));
+	});
+}
+
+#[test]
+fn test_idle_players_draw() {
+	new_test_ext().execute_with(|| {
+		let player_1: u64 = 1;
+		let player_2: u64 = 2;
+
+		run_to_block(100);
+
+		assert_ok!(RPSOnline::queue(Origin::signed(player_1)));
+		assert_ok!(RPSOnline::queue(Origin::signed(player_2)));
+		run_next_block();
+		let game_id = RPSOnline::player_game(player_1);
+
+		// no player initiates in time, the game is a draw without a winner
+		run_to_block(101 + MAX_BLOCKS_PER_TURN as u64);
+		assert_eq!(RPSOnline::games(game_id).game_state, GameState::Draw);
+		assert_eq!(MatchMaker::ratings(player_1), 1500);
+		assert_eq!(MatchMaker::ratings(player_2), 1500);
+
+		// a draw is cleaned up like any finished game
+		run_to_block(101 + (MAX_BLOCKS_PER_TURN + CLEANUP_BOARDS_AFTER) as u64);
+		assert!(!Games::<Test>::contains_key(game_id));
+		assert!(!PlayerGame::<Test>::contains_key(player_1));
+		assert!(!PlayerGame::<Test>::contains_key(player_2));
+	});
+}
+
+#[test]
+fn test_new_game_is_not_rated() {
+	new_test_ext().execute_with(|| {
+		let player_1: u64 = 1;
+		let player_2: u64 = 2;
+
+		run_to_block(100);
+
+		// a game against an opponent of choice doesn't change the ratings
+		assert_ok!(RPSOnline::new_game(Origin::signed(player_1), player_2));
+		let game_id = RPSOnline::player_game(player_1);
+		assert_eq!(RPSOnline::game_match(game_id), None);
+		assert_ok!(RPSOnline::initiate(Origin::signed(player_1)));
+		run_to_block(100 + MAX_BLOCKS_PER_TURN as u64);
+		assert_eq!(RPSOnline::games(game_id).game_state, GameState::Finished(player_1));
+		assert_eq!(MatchMaker::ratings(player_1), 1500);
+		assert_eq!(MatchMaker::ratings(player_2), 1500);
+	});
+}
+
+#[test]
+fn test_leave_queue() {
+	new_test_ext().execute_with(|| {
+		let player_1: u64 = 1;
+		let player_2: u64 = 2;
+
+		let current_block: u64 = 100;
+
+		// start from block 100
+		run_to_block(current_block);
+
+		// can't leave without being queued
+		assert_noop!(
+			RPSOnline::leave_queue(Origin::signed(player_1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up player 1 and leave again
+		assert_ok!(RPSOnline::queue(Origin::signed(player_1)));
+		assert_ok!(RPSOnline::leave_queue(Origin::signed(player_1)));
+		assert_noop!(
+			RPSOnline::leave_queue(Origin::signed(player_1)),
+			pallet_matchmaker::Error::<Test>::NotQueued
+		);
+
+		// queue up player 2, no match as player 1 left
+		assert_ok!(RPSOnline::queue(Origin::signed(player_2)));
+
+		run_next_block();
+
+		assert!(!PlayerGame::<Test>::contains_key(player_1));
+		assert!(!PlayerGame::<Test>::contains_key(player_2));
+	});
+}
+
+#[test]
+fn test_party_with_open_game() {
+	new_test_ext().execute_with(|| {
+		let player_1: u64 = 1;
+		let player_2: u64 = 2;
+		let player_3: u64 = 3;
+
+		run_to_block(100);
+
+		// player 1 has a game open and joins the party of player 3
+		assert_ok!(RPSOnline::new_game(Origin::signed(player_1), player_2));
+		let game_id = RPSOnline::player_game(player_1);
+		assert_ok!(MatchMaker::create_party(Origin::signed(player_3)));
+		assert_ok!(MatchMaker::invite_to_party(Origin::signed(player_3), player_1));
+		assert_ok!(MatchMaker::join_party(Origin::signed(player_1), player_3));
+
+		// the leader can't queue a party with a member that has a game open
+		assert_noop!(RPSOnline::queue(Origin::signed(player_3)), Error::<Test>::PlayerHasGame);
+
+		// a match with a player that has a game open is refused, the game is kept
+		assert!(!RPSOnline::on_matched(0, 0, vec![player_1, player_3]).0);
+		assert_eq!(RPSOnline::player_game(player_1), game_id);
+		assert!(!PlayerGame::<Test>::contains_key(player_3));
+	});
+}